@@ -45,7 +45,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         version_major: 2.into(),
         version_minor: 15.into(),
         canonical_name: lib_name,
-        procedures: vec![proc],
+        procedures: vec![proc.into()],
         ..Library::default()
     };
 
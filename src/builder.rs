@@ -2,8 +2,12 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::hash::Hash;
 
+use declio::Encode;
+
 use crate::component::{Component, Library, NonNullableMode, SourceInfo};
-use crate::expr::Constant;
+use crate::expr::{Constant, Expr, LabeledExpr};
+use crate::metadata::{Metadata, MetadataRepository, MetadataTarget};
+use crate::node::{NamedType, Nullable, Type};
 use crate::prim::*;
 
 #[derive(Debug)]
@@ -13,6 +17,7 @@ pub struct ComponentBuilder {
     constants: Vec<Constant>,
     canonical_names: IndexMap<CanonicalName>,
     strings: IndexMap<StrRef>,
+    metadata: Vec<MetadataRepository>,
 }
 
 impl ComponentBuilder {
@@ -58,6 +63,83 @@ impl ComponentBuilder {
         }
     }
 
+    /// Attaches a metadata payload to a node, creating the repository for the tag when needed.
+    pub fn add_metadata(&mut self, tag: StrRef, node: MetadataTarget, payload: Vec<u8>) {
+        let tag = self.add_string(tag);
+        let repo = match self.metadata.iter().position(|repo| repo.tag == tag) {
+            Some(idx) => &mut self.metadata[idx],
+            None => {
+                self.metadata.push(MetadataRepository::new(tag));
+                self.metadata.last_mut().unwrap()
+            }
+        };
+        repo.attach(node, payload);
+    }
+
+    pub fn add_typed_metadata<M: Metadata>(
+        &mut self,
+        node: MetadataTarget,
+        metadata: &M,
+    ) -> Result<(), declio::Error> {
+        let mut payload = vec![];
+        metadata.encode((), ENDIANESS, &mut payload)?;
+        self.add_metadata(StrRef::Borrowed(M::TAG), node, payload);
+        Ok(())
+    }
+
+    /// Builds a record type, sorting its named fields by name as the kernel format expects.
+    pub fn record_type(
+        &mut self,
+        nullable: Nullable,
+        positional: Vec<Type>,
+        mut named: Vec<(StrRef, Type)>,
+    ) -> Type {
+        named.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+        let named = named
+            .into_iter()
+            .map(|(name, typ)| NamedType {
+                name: self.add_string(name),
+                typ,
+                flags: 0,
+            })
+            .collect();
+        Type::Record {
+            nullable,
+            positional,
+            named,
+        }
+    }
+
+    /// Builds a record literal from its fields along with their static types, which make up the
+    /// type of the record.
+    pub fn record_literal(
+        &mut self,
+        offset: FileOffset,
+        positional: Vec<(Expr, Type)>,
+        mut named: Vec<(StrRef, Expr, Type)>,
+    ) -> Expr {
+        named.sort_by(|(a, _, _), (b, _, _)| a.as_bytes().cmp(b.as_bytes()));
+        let (positional, positional_types): (Vec<_>, Vec<_>) = positional.into_iter().unzip();
+        let mut named_types = Vec::with_capacity(named.len());
+        let named = named
+            .into_iter()
+            .map(|(name, value, typ)| {
+                named_types.push((name.clone(), typ));
+                LabeledExpr {
+                    label: self.add_string(name),
+                    value,
+                }
+            })
+            .collect();
+        let record_type = self.record_type(Nullable::False, positional_types, named_types);
+        Expr::RecordLit {
+            offset,
+            positional,
+            named,
+            record_type: Box::new(record_type),
+        }
+    }
+
     pub fn add_library(&mut self, library: Library) {
         self.libraries.push(library);
     }
@@ -83,7 +165,7 @@ impl ComponentBuilder {
             source_map: self.sources,
             constants: self.constants,
             canonical_names: self.canonical_names.into_vec(),
-            payloads: vec![],
+            metadata: self.metadata,
             strings: self.strings.into_vec(),
             main_method,
             non_nullable_mode,
@@ -101,6 +183,7 @@ impl Default for ComponentBuilder {
             constants: vec![],
             canonical_names: IndexMap::default(),
             strings,
+            metadata: vec![],
         }
     }
 }
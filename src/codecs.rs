@@ -7,24 +7,26 @@ pub mod list {
     use crate::prim::Uint;
 
     #[inline]
-    pub fn encode<W, A>(vec: &[A], ctx: (), endian: Endian, writer: &mut W) -> Result<(), Error>
+    pub fn encode<W, A, C>(vec: &[A], ctx: C, endian: Endian, writer: &mut W) -> Result<(), Error>
     where
         W: io::Write,
-        A: Encode,
+        A: Encode<C>,
+        C: Copy,
     {
         let len = vec.len();
-        Uint(len as u32).encode(ctx, endian, writer)?;
+        Uint(len as u32).encode((), endian, writer)?;
         vec.encode((Len(vec.len()), ctx), endian, writer)
     }
 
     #[inline]
-    pub fn decode<R, A>(ctx: (), endian: Endian, reader: &mut R) -> Result<Vec<A>, Error>
+    pub fn decode<R, A, C>(ctx: C, endian: Endian, reader: &mut R) -> Result<Vec<A>, Error>
     where
         R: io::Read,
-        A: Decode,
+        A: Decode<C>,
+        C: Copy,
     {
-        let len = Uint::decode(ctx, endian, reader)?;
-        <Vec<A>>::decode(Len(len.0 as usize), endian, reader)
+        let len = Uint::decode((), endian, reader)?;
+        <Vec<A>>::decode((Len(len.0 as usize), ctx), endian, reader)
     }
 }
 
@@ -34,31 +36,31 @@ pub mod option {
     use declio::ctx::Endian;
     use declio::{Decode, Encode, Error};
 
-    pub fn encode<W, A>(
+    pub fn encode<W, A, C>(
         opt: &Option<A>,
-        ctx: (),
+        ctx: C,
         endian: Endian,
         writer: &mut W,
     ) -> Result<(), Error>
     where
         W: io::Write,
-        A: Encode,
+        A: Encode<C>,
     {
         match opt {
             Some(val) => {
-                1u8.encode(ctx, endian, writer)?;
+                1u8.encode((), endian, writer)?;
                 val.encode(ctx, endian, writer)
             }
-            None => 0u8.encode(ctx, endian, writer),
+            None => 0u8.encode((), endian, writer),
         }
     }
 
-    pub fn decode<R, A>(ctx: (), endian: Endian, reader: &mut R) -> Result<Option<A>, Error>
+    pub fn decode<R, A, C>(ctx: C, endian: Endian, reader: &mut R) -> Result<Option<A>, Error>
     where
         R: io::Read,
-        A: Decode,
+        A: Decode<C>,
     {
-        match u8::decode(ctx, endian, reader)? {
+        match u8::decode((), endian, reader)? {
             0 => Ok(None),
             1 => Ok(Some(A::decode(ctx, endian, reader)?)),
             other => Err(Error::new(format_args!("Invalid option pattern {}", other))),
@@ -66,6 +68,79 @@ pub mod option {
     }
 }
 
+/// Lists of library and class members, which add the index of the failing member to the path of
+/// decoding errors.
+pub mod members {
+    use std::io;
+
+    use declio::ctx::Endian;
+    use declio::{Decode, Encode, Error};
+
+    use crate::error::{within_node, PathSegment};
+    use crate::prim::Uint;
+
+    pub trait Member {
+        fn segment(index: usize) -> PathSegment;
+    }
+
+    #[inline]
+    pub fn encode<W, A, C>(vec: &[A], ctx: C, endian: Endian, writer: &mut W) -> Result<(), Error>
+    where
+        W: io::Write,
+        A: Encode<C>,
+        C: Copy,
+    {
+        super::list::encode(vec, ctx, endian, writer)
+    }
+
+    pub fn decode<R, A, C>(ctx: C, endian: Endian, reader: &mut R) -> Result<Vec<A>, Error>
+    where
+        R: io::Read,
+        A: Decode<C> + Member,
+        C: Copy,
+    {
+        let len = Uint::decode((), endian, reader)?;
+        (0..len.0 as usize)
+            .map(|i| within_node(A::decode(ctx, endian, reader), || A::segment(i)))
+            .collect()
+    }
+}
+
+/// Optional function body, which adds [`PathSegment::Body`](crate::PathSegment::Body) to the path
+/// of decoding errors.
+pub mod body {
+    use std::io;
+
+    use declio::ctx::Endian;
+    use declio::{Decode, Encode, Error};
+
+    use crate::error::{within_node, PathSegment};
+
+    #[inline]
+    pub fn encode<W, A, C>(
+        opt: &Option<A>,
+        ctx: C,
+        endian: Endian,
+        writer: &mut W,
+    ) -> Result<(), Error>
+    where
+        W: io::Write,
+        A: Encode<C>,
+    {
+        super::option::encode(opt, ctx, endian, writer)
+    }
+
+    pub fn decode<R, A, C>(ctx: C, endian: Endian, reader: &mut R) -> Result<Option<A>, Error>
+    where
+        R: io::Read,
+        A: Decode<C>,
+    {
+        within_node(super::option::decode(ctx, endian, reader), || {
+            PathSegment::Body
+        })
+    }
+}
+
 pub mod bytes {
     use std::io;
 
@@ -91,3 +166,229 @@ pub mod bytes {
         Ok(bytes)
     }
 }
+
+/// Offset table trailing a library or class: `count + 1` offsets, the last one being the end of
+/// the last item, followed by the item count.
+pub mod offsets {
+    use std::io;
+
+    use declio::ctx::{Endian, Len};
+    use declio::{Decode, Encode, Error};
+
+    #[inline]
+    pub fn encode<W>(offsets: &[u32], endian: Endian, writer: &mut W) -> Result<(), Error>
+    where
+        W: io::Write,
+    {
+        offsets.encode(Len(offsets.len()), endian, writer)?;
+        (offsets.len() as u32 - 1).encode((), endian, writer)
+    }
+
+    #[inline]
+    pub fn decode<R>(count: usize, endian: Endian, reader: &mut R) -> Result<Vec<u32>, Error>
+    where
+        R: io::Read,
+    {
+        let offsets = <Vec<u32>>::decode(Len(count + 1), endian, reader)?;
+        let size = u32::decode((), endian, reader)?;
+        if size as usize != count {
+            Err(Error::new("Index does not match the item count"))
+        } else {
+            Ok(offsets)
+        }
+    }
+}
+
+/// Defines an enum of nodes tagged by their first byte along with its codecs and its `TAGS`
+/// registry. Unlike derived codecs, they add the failing variant to the path of decoding errors
+/// and pass every node to `on_encode` before writing it, which lets the writer see nodes it does
+/// not encode itself. Variants are tagged with `#[tag = N]`, fields take the `with` codecs of
+/// derived structs and tuple variants hold at most three fields.
+macro_rules! node_enum {
+    (
+        $(on_encode = $hook:path;)?
+        $(#[$attr:meta])*
+        pub enum $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                #[tag = $tag:literal]
+                $variant:ident
+                $({
+                    $(
+                        $(#[doc = $field_doc:literal])*
+                        $(#[declio(with = $with:tt)])?
+                        $field:ident: $field_ty:ty
+                    ),* $(,)?
+                })?
+                $(( $($elem_ty:ty),* $(,)? ))?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        pub enum $name {
+            $(
+                $(#[doc = $doc])*
+                $variant
+                $({ $( $(#[doc = $field_doc])* $field: $field_ty ),* })?
+                $(( $($elem_ty),* ))?,
+            )*
+        }
+
+        impl $crate::declio::Encode for $name {
+            fn encode<W>(
+                &self,
+                _ctx: (),
+                endian: $crate::declio::ctx::Endian,
+                writer: &mut W,
+            ) -> Result<(), $crate::declio::Error>
+            where
+                W: std::io::Write,
+            {
+                $( $hook(self)?; )?
+                match self {
+                    $(
+                        node @ $name::$variant { .. } => {
+                            $crate::declio::Encode::encode(&$tag, (), endian, writer)?;
+                            $crate::codecs::node_enum!(
+                                @encode node, endian, writer, $name, $variant,
+                                $({ $( [$($with)?] $field ),* })? $(( $($elem_ty),* ))?
+                            )
+                        }
+                    )*
+                }
+                Ok(())
+            }
+        }
+
+        impl $crate::declio::Decode for $name {
+            fn decode<R>(
+                _ctx: (),
+                endian: $crate::declio::ctx::Endian,
+                reader: &mut R,
+            ) -> Result<Self, $crate::declio::Error>
+            where
+                R: std::io::Read,
+            {
+                let tag: u8 = $crate::declio::Decode::decode((), endian, reader)?;
+                let node = Self::decode_variant(tag, endian, reader);
+                $crate::error::within_node(node, || {
+                    $crate::error::PathSegment::Node(match tag {
+                        $( $tag => concat!(stringify!($name), "::", stringify!($variant)), )*
+                        _ => stringify!($name),
+                    })
+                })
+            }
+        }
+
+        impl $name {
+            /// Tags of the variants, in the order they are declared.
+            pub const TAGS: &'static [$crate::tags::Tag] = &[
+                $( $crate::tags::Tag { variant: stringify!($variant), id: $tag }, )*
+            ];
+
+            /// Tag written before the fields of this node.
+            pub fn tag(&self) -> u8 {
+                match self {
+                    $( $name::$variant { .. } => $tag, )*
+                }
+            }
+
+            fn decode_variant<R>(
+                tag: u8,
+                endian: $crate::declio::ctx::Endian,
+                reader: &mut R,
+            ) -> Result<Self, $crate::declio::Error>
+            where
+                R: std::io::Read,
+            {
+                let node = match tag {
+                    $(
+                        $tag => $crate::codecs::node_enum!(
+                            @decode endian, reader, $name, $variant,
+                            $({ $( [$($with)?] $field ),* })? $(( $($elem_ty),* ))?
+                        ),
+                    )*
+                    _ => {
+                        return Err($crate::declio::Error::new(format_args!(
+                            "Unknown {} tag {}",
+                            stringify!($name),
+                            tag
+                        )))
+                    }
+                };
+                Ok(node)
+            }
+        }
+    };
+
+    (@encode $node:ident, $endian:ident, $writer:ident, $name:ident, $variant:ident,) => {{
+        let _ = $node;
+    }};
+    (@encode $node:ident, $endian:ident, $writer:ident, $name:ident, $variant:ident,
+        { $( [$($with:tt)?] $field:ident ),* }
+    ) => {
+        if let $name::$variant { $($field),* } = $node {
+            $( $crate::codecs::node_enum!(@encode_field $field, $endian, $writer, $($with)?); )*
+        }
+    };
+    (@encode $node:ident, $endian:ident, $writer:ident, $name:ident, $variant:ident,
+        ($a:ty)
+    ) => {
+        if let $name::$variant(a) = $node {
+            $crate::declio::Encode::encode(a, (), $endian, $writer)?;
+        }
+    };
+    (@encode $node:ident, $endian:ident, $writer:ident, $name:ident, $variant:ident,
+        ($a:ty, $b:ty)
+    ) => {
+        if let $name::$variant(a, b) = $node {
+            $crate::declio::Encode::encode(a, (), $endian, $writer)?;
+            $crate::declio::Encode::encode(b, (), $endian, $writer)?;
+        }
+    };
+    (@encode $node:ident, $endian:ident, $writer:ident, $name:ident, $variant:ident,
+        ($a:ty, $b:ty, $c:ty)
+    ) => {
+        if let $name::$variant(a, b, c) = $node {
+            $crate::declio::Encode::encode(a, (), $endian, $writer)?;
+            $crate::declio::Encode::encode(b, (), $endian, $writer)?;
+            $crate::declio::Encode::encode(c, (), $endian, $writer)?;
+        }
+    };
+
+    (@decode $endian:ident, $reader:ident, $name:ident, $variant:ident,) => {
+        $name::$variant
+    };
+    (@decode $endian:ident, $reader:ident, $name:ident, $variant:ident,
+        { $( [$($with:tt)?] $field:ident ),* }
+    ) => {
+        $name::$variant {
+            $( $field: $crate::codecs::node_enum!(@decode_field $endian, $reader, $($with)?) ),*
+        }
+    };
+    (@decode $endian:ident, $reader:ident, $name:ident, $variant:ident, ($($elem_ty:ty),*)) => {
+        $name::$variant($( <$elem_ty as $crate::declio::Decode>::decode((), $endian, $reader)? ),*)
+    };
+
+    (@encode_field $field:ident, $endian:ident, $writer:ident, "codecs::list") => {
+        $crate::codecs::list::encode($field, (), $endian, $writer)?
+    };
+    (@encode_field $field:ident, $endian:ident, $writer:ident, "codecs::option") => {
+        $crate::codecs::option::encode($field, (), $endian, $writer)?
+    };
+    (@encode_field $field:ident, $endian:ident, $writer:ident,) => {
+        $crate::declio::Encode::encode($field, (), $endian, $writer)?
+    };
+
+    (@decode_field $endian:ident, $reader:ident, "codecs::list") => {
+        $crate::codecs::list::decode((), $endian, $reader)?
+    };
+    (@decode_field $endian:ident, $reader:ident, "codecs::option") => {
+        $crate::codecs::option::decode((), $endian, $reader)?
+    };
+    (@decode_field $endian:ident, $reader:ident,) => {
+        $crate::declio::Decode::decode((), $endian, $reader)?
+    };
+}
+
+pub(crate) use node_enum;
@@ -1,78 +1,900 @@
 use core::fmt;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::Path;
 
-use declio::ctx::Len;
+use declio::ctx::{Endian, Len};
 use declio::{Decode, Encode};
 use hex_magic::hex;
 use wtf8::Wtf8;
 
+use crate::error::{take_node_path, Error, PathSegment, PositionReader};
 use crate::expr::{Constant, Expr};
-use crate::flags::{DependencyFlags, LibraryFlags};
-use crate::node::{Class, Combinator, Extension, Field, Procedure, Typedef};
+use crate::flags::{ClassFlags, DependencyFlags, LibraryFlags, ProcedureFlags};
+use crate::metadata::{ExprOffsets, Metadata, MetadataEntry, MetadataRepository, MetadataTarget};
+use crate::node::{
+    Class, ClassIndex, Combinator, Extension, ExtensionTypeDeclaration, Field, Procedure, ProcedureKind, ProcedureStubKind, RedirectingFactory, Typedef
+};
 use crate::prim::*;
 use crate::{codecs, writer};
 
 pub struct ComponentFile<R> {
     source: R,
+    range: Range<u64>,
+    header: ComponentHeader,
     index: ComponentIndex,
+    lenient: bool,
+    metadata: Option<Vec<(Option<String>, MetadataRepository)>>,
 }
 
 impl ComponentFile<BufReader<File>> {
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, declio::Error> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::open_with(path, &LoadOptions::default())
+    }
+
+    pub fn open_with<P: AsRef<Path>>(path: P, opts: &LoadOptions) -> Result<Self, Error> {
         let source = BufReader::new(File::open(path)?);
-        Self::load(source)
+        Self::load_with(source, opts)
+    }
+
+    /// Opens every component of a concatenated dill, each with its own file handle.
+    pub fn open_all<P: AsRef<Path>>(path: P, opts: &LoadOptions) -> Result<Vec<Self>, Error> {
+        let path = path.as_ref();
+        let ranges = Self::component_ranges(&mut BufReader::new(File::open(path)?))?;
+        ranges
+            .into_iter()
+            .map(|range| {
+                let source = BufReader::new(File::open(path)?);
+                Self::load_ending_at(source, range.end, opts)
+            })
+            .collect()
+    }
+}
+
+impl<B: AsRef<[u8]>> ComponentFile<Cursor<B>> {
+    /// Loads a component from an in-memory buffer, which enables the borrowing accessors.
+    pub fn from_bytes(bytes: B) -> Result<Self, Error> {
+        Self::load(Cursor::new(bytes))
+    }
+
+    /// Bytes of this component within the underlying buffer.
+    pub fn bytes(&self) -> &[u8] {
+        let range = self.range.start as usize..self.range.end as usize;
+        &self.source.get_ref().as_ref()[range]
+    }
+
+    /// Reads the string table without copying the strings out of the buffer.
+    pub fn string_table_ref(&self) -> Result<StringTableRef<'_>, Error> {
+        let table = StringTableRef::decode(&mut self.reader_at(self.index.string_table_offset))?;
+        Ok(table)
+    }
+
+    /// Reads the source map without copying the sources out of the buffer.
+    pub fn source_map_ref(&self) -> Result<Vec<SourceInfoRef<'_>>, Error> {
+        let mut reader = self.reader_at(self.index.source_table_offset);
+        let length = u32::decode((), ENDIANESS, &mut reader)?;
+        (0..length)
+            .map(|_| Ok(SourceInfoRef::decode(&mut reader)?))
+            .collect()
+    }
+
+    /// Decodes a library through a shared reference, so that several threads can decode the
+    /// libraries of the same buffer. Honors [`LoadOptions::lenient`] like
+    /// [`ComponentFile::libraries`].
+    pub fn library_at(&self, index: usize) -> Result<Library, Error> {
+        let range = match self.index.library_offsets.get(index..index + 2) {
+            Some(&[start, end]) => start..end,
+            _ => {
+                let err = declio::Error::new(format_args!("No library at index {index}"));
+                return Err(err.into());
+            }
+        };
+        self.view().decode_library(index, range)
+    }
+
+    fn reader_at(&self, offset: u32) -> Cursor<&[u8]> {
+        let mut reader = Cursor::new(self.bytes());
+        reader.set_position(offset.into());
+        reader
+    }
+
+    /// Component file reading from a borrowed cursor over the same buffer.
+    fn view(&self) -> ComponentFile<Cursor<&[u8]>> {
+        ComponentFile {
+            source: Cursor::new(self.source.get_ref().as_ref()),
+            range: self.range.clone(),
+            header: self.header.clone(),
+            index: self.index.clone(),
+            lenient: self.lenient,
+            metadata: None,
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<B: AsRef<[u8]> + Sync> ComponentFile<Cursor<B>> {
+    /// Decodes all libraries concurrently, each thread reading from its own cursor over the
+    /// buffer. Honors [`LoadOptions::lenient`] like [`ComponentFile::libraries`].
+    pub fn libraries_par(&self) -> Result<Vec<Library>, Error> {
+        use rayon::prelude::*;
+
+        self.index
+            .library_offsets
+            .par_windows(2)
+            .enumerate()
+            .map_init(
+                || self.view(),
+                |file, (i, range)| file.decode_library(i, range[0]..range[1]),
+            )
+            .collect()
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl ComponentFile<Cursor<memmap2::Mmap>> {
+    /// Maps a dill file into memory.
+    ///
+    /// # Safety
+    /// The file must not be modified while it is mapped.
+    pub unsafe fn map<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mmap = memmap2::Mmap::map(&File::open(path)?)?;
+        Self::from_bytes(mmap)
     }
 }
 
 impl<R: Seek + Read> ComponentFile<R> {
-    pub fn load(mut source: R) -> Result<Self, declio::Error> {
-        source.seek(SeekFrom::End(-(ComponentMetadata::SIZE as i64)))?;
-        let meta = ComponentMetadata::decode((), ENDIANESS, &mut source)?;
+    pub fn load(source: R) -> Result<Self, Error> {
+        Self::load_with(source, &LoadOptions::default())
+    }
+
+    /// Loads a stream that holds exactly one component.
+    pub fn load_with(mut source: R, opts: &LoadOptions) -> Result<Self, Error> {
+        let stream_len = source.seek(SeekFrom::End(0))?;
+        let file = Self::load_ending_at(source, stream_len, opts)?;
+        if file.range.start != 0 {
+            return Err(declio::Error::new(format_args!(
+                "Component size {} does not match the stream length {}, \
+                 use load_all for concatenated components",
+                stream_len - file.range.start,
+                stream_len
+            ))
+            .into());
+        }
+        Ok(file)
+    }
+
+    /// Loads every component of a concatenated dill, in the order they appear in the stream.
+    pub fn load_all(mut source: R, opts: &LoadOptions) -> Result<Vec<Self>, Error>
+    where
+        R: Clone,
+    {
+        Self::component_ranges(&mut source)?
+            .into_iter()
+            .map(|range| Self::load_ending_at(source.clone(), range.end, opts))
+            .collect()
+    }
+
+    /// Returns the byte range of each component in a stream of concatenated components by
+    /// walking back through their trailing [`ComponentMetadata`].
+    pub fn component_ranges(source: &mut R) -> Result<Vec<Range<u64>>, Error> {
+        let mut ranges = vec![];
+        let mut end = source.seek(SeekFrom::End(0))?;
+
+        while end > 0 {
+            let meta = ComponentMetadata::read_ending_at(source, end)?;
+            let start = end
+                .checked_sub(meta.file_size.into())
+                .filter(|_| meta.file_size as usize >= ComponentMetadata::SIZE)
+                .ok_or_else(|| {
+                    declio::Error::new(format_args!(
+                        "Invalid component size {} at offset {}",
+                        meta.file_size, end
+                    ))
+                })?;
+            ranges.push(start..end);
+            end = start;
+        }
+        ranges.reverse();
+        Ok(ranges)
+    }
+
+    fn load_ending_at(mut source: R, end: u64, opts: &LoadOptions) -> Result<Self, Error> {
+        let meta = ComponentMetadata::read_ending_at(&mut source, end)?;
+        let start = end.checked_sub(meta.file_size.into()).ok_or_else(|| {
+            declio::Error::new(format_args!(
+                "Component size {} exceeds the stream length {}",
+                meta.file_size, end
+            ))
+        })?;
+
+        source.seek(SeekFrom::Start(start))?;
+        let header = ComponentHeader::decode((), ENDIANESS, &mut source)?;
+        opts.check_header(&header)?;
 
         let index_offset = ComponentIndex::MINIMUM_SIZE
             + meta.library_count as usize * 4
             + ComponentMetadata::SIZE;
-        source.seek(SeekFrom::End(-(index_offset as i64)))?;
+        source.seek(SeekFrom::Start(end - index_offset as u64))?;
         let index = ComponentIndex::decode(meta.library_count, ENDIANESS, &mut source)?;
 
-        Ok(Self { source, index })
+        Ok(Self {
+            source,
+            range: start..end,
+            header,
+            index,
+            lenient: opts.lenient,
+            metadata: None,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &ComponentHeader {
+        &self.header
+    }
+
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.header.format_version()
+    }
+
+    /// Byte range of this component within the underlying stream.
+    #[inline]
+    pub fn range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+
+    pub fn libraries(&mut self) -> Result<Vec<Library>, Error> {
+        let offsets = self.index.library_offsets.clone();
+        offsets
+            .windows(2)
+            .enumerate()
+            .map(|(i, range)| self.decode_library(i, range[0]..range[1]))
+            .collect()
+    }
+
+    /// Lists libraries along with their classes and procedures using the offset tables, without
+    /// decoding any bodies. Entries can be decoded on demand with [`ComponentFile::library`],
+    /// [`ComponentFile::class`] and [`ComponentFile::procedure`].
+    pub fn library_entries(&mut self) -> Result<Vec<LibraryEntry>, Error> {
+        let offsets = self.index.library_offsets.clone();
+        offsets
+            .windows(2)
+            .map(|range| self.library_entry(range[0]..range[1]))
+            .collect()
+    }
+
+    pub fn library(&mut self, entry: &LibraryEntry) -> Result<Library, Error> {
+        let index = self
+            .index
+            .library_offsets
+            .windows(2)
+            .position(|range| range[0] == entry.range.start && range[1] == entry.range.end)
+            .ok_or_else(|| {
+                let start = entry.range.start;
+                declio::Error::new(format_args!(
+                    "No library of this component starts at {start}"
+                ))
+            })?;
+        self.decode_library(index, entry.range.clone())
+    }
+
+    pub fn class(&mut self, entry: &ClassEntry) -> Result<Class, Error> {
+        let version = self.version();
+        self.decode_at(entry.range.start, |reader| {
+            Class::decode(version, ENDIANESS, reader)
+        })
+        .map_err(|err| {
+            let path = self.class_path(entry, err.offset());
+            err.within(path)
+        })
     }
 
-    pub fn libraries(&mut self) -> Result<Vec<Library>, declio::Error> {
-        let mut libs = Vec::with_capacity(self.index.library_offsets.len());
+    pub fn procedure(&mut self, entry: &ProcedureEntry) -> Result<Procedure, Error> {
+        self.decode_at(entry.range.start, |reader| {
+            Procedure::decode((), ENDIANESS, reader)
+        })
+        .map_err(|err| err.within([PathSegment::Procedure(self.name_of(entry.name))]))
+    }
 
-        for offset in &self.index.library_offsets[0..self.index.library_offsets.len() - 1] {
-            self.source.seek(SeekFrom::Start((*offset).into()))?;
-            libs.push(Library::decode((), ENDIANESS, &mut self.source)?);
+    fn decode_library(&mut self, index: usize, range: Range<u32>) -> Result<Library, Error> {
+        if self.lenient {
+            return self.decode_library_lenient(index, range);
         }
-        Ok(libs)
+        let version = self.version();
+        self.decode_at(range.start, |reader| {
+            Library::decode(version, ENDIANESS, reader)
+        })
+        .map_err(|err| self.library_path(err, index, range))
     }
 
-    pub fn string_table(&mut self) -> Result<StringTable, declio::Error> {
-        self.source
-            .seek(SeekFrom::Start(self.index.string_table_offset.into()))?;
-        StringTable::decode((), ENDIANESS, &mut self.source)
+    /// Decodes a library section by section, seeking to every class and procedure through the
+    /// offset tables so that the ones that fail to decode can be kept as raw bytes.
+    fn decode_library_lenient(
+        &mut self,
+        index: usize,
+        range: Range<u32>,
+    ) -> Result<Library, Error> {
+        let version = self.version();
+        let entry = self
+            .library_entry(range.clone())
+            .map_err(|err| err.within([PathSegment::Library(index)]))?;
+        let offsets = &entry.index;
+
+        let mut lib = self
+            .decode_at(range.start, |reader| {
+                Ok(Library {
+                    flags: LibraryFlags::decode((), ENDIANESS, reader)?,
+                    version_major: Uint::decode((), ENDIANESS, reader)?,
+                    version_minor: Uint::decode((), ENDIANESS, reader)?,
+                    canonical_name: CanonicalNameRef::decode((), ENDIANESS, reader)?,
+                    name: StringRef::decode((), ENDIANESS, reader)?,
+                    file_uri: UriRef::decode((), ENDIANESS, reader)?,
+                    problems: codecs::list::decode((), ENDIANESS, reader)?,
+                    annotations: codecs::list::decode((), ENDIANESS, reader)?,
+                    dependencies: codecs::list::decode((), ENDIANESS, reader)?,
+                    additional_exports: codecs::list::decode((), ENDIANESS, reader)?,
+                    library_parts: codecs::list::decode((), ENDIANESS, reader)?,
+                    typedefs: codecs::members::decode((), ENDIANESS, reader)?,
+                    ..Library::default()
+                })
+            })
+            .map_err(|err| self.library_path(err, index, range.clone()))?;
+
+        for class in &entry.classes {
+            let class = self.decode_class_lenient(index, range.clone(), class)?;
+            lib.classes.push(class);
+        }
+
+        let members_offset = end_offset("class", &offsets.class_offsets)
+            .map_err(|err| Error::from(err).within([PathSegment::Library(index)]))?;
+        let (extensions, extension_types, fields) = self
+            .decode_at(members_offset, |reader| {
+                let extensions: Vec<Extension> =
+                    codecs::members::decode(version, ENDIANESS, reader)?;
+                let extension_types: Vec<ExtensionTypeDeclaration> =
+                    if version >= Version::EXTENSION_TYPES {
+                        codecs::members::decode((), ENDIANESS, reader)?
+                    } else {
+                        vec![]
+                    };
+                let fields: Vec<Field> = codecs::members::decode((), ENDIANESS, reader)?;
+                Ok((extensions, extension_types, fields))
+            })
+            .map_err(|err| self.library_path(err, index, range.clone()))?;
+        lib.extensions = extensions;
+        lib.extension_types = extension_types;
+        lib.fields = fields;
+
+        for proc in offsets.procedure_offsets.windows(2) {
+            let proc = self.decode_or_keep(index, range.clone(), proc[0]..proc[1], |reader| {
+                Procedure::decode((), ENDIANESS, reader)
+            })?;
+            lib.procedures.push(proc);
+        }
+
+        lib.source_refs = self
+            .decode_at(offsets.source_refs_offset, |reader| {
+                codecs::list::decode((), ENDIANESS, reader)
+            })
+            .map_err(|err| self.library_path(err, index, range.clone()))?;
+        lib.index = entry.index;
+        Ok(lib)
     }
 
-    pub fn constants(&mut self) -> Result<Vec<Constant>, declio::Error> {
-        self.source
-            .seek(SeekFrom::Start(self.index.constant_table_offset.into()))?;
-        codecs::list::decode((), ENDIANESS, &mut self.source)
+    /// Decodes a class around its procedures, which are kept as raw bytes when they fail to
+    /// decode. The whole class is kept as raw bytes when any other part of it fails.
+    fn decode_class_lenient(
+        &mut self,
+        library: usize,
+        library_range: Range<u32>,
+        entry: &ClassEntry,
+    ) -> Result<MaybeDecoded<Class>, Error> {
+        let version = self.version();
+        let offsets = &entry.index.procedure_offsets;
+        let head = self.decode_at(entry.range.start, |reader| {
+            Ok(Class {
+                tag: ClassTag::decode((), ENDIANESS, reader)?,
+                canonical_name: CanonicalNameRef::decode((), ENDIANESS, reader)?,
+                file_uri: UriRef::decode((), ENDIANESS, reader)?,
+                start_offset: FileOffset::decode((), ENDIANESS, reader)?,
+                definition_range: FileRange::decode((), ENDIANESS, reader)?,
+                flags: ClassFlags::decode(version, ENDIANESS, reader)?,
+                name: StringRef::decode((), ENDIANESS, reader)?,
+                annotations: codecs::list::decode((), ENDIANESS, reader)?,
+                type_params: codecs::list::decode((), ENDIANESS, reader)?,
+                super_class: codecs::option::decode((), ENDIANESS, reader)?,
+                mixed_in_type: codecs::option::decode((), ENDIANESS, reader)?,
+                implemented_classes: codecs::list::decode((), ENDIANESS, reader)?,
+                fields: codecs::members::decode((), ENDIANESS, reader)?,
+                constructors: codecs::members::decode((), ENDIANESS, reader)?,
+                ..Class::default()
+            })
+        });
+        let factories_offset = end_offset("procedure", offsets).map_err(|err| {
+            Error::from(err).within([
+                PathSegment::Library(library),
+                PathSegment::Class(self.name_of(entry.name)),
+            ])
+        })?;
+        let factories = self.decode_at(factories_offset, |reader| {
+            codecs::members::decode((), ENDIANESS, reader)
+        });
+        let (mut class, redirecting_factories) = match (head, factories) {
+            (Ok(class), Ok(factories)) => (class, factories),
+            _ => {
+                return self.decode_or_keep(library, library_range, entry.range.clone(), |reader| {
+                    Class::decode(version, ENDIANESS, reader)
+                })
+            }
+        };
+
+        for proc in offsets.windows(2) {
+            let proc =
+                self.decode_or_keep(library, library_range.clone(), proc[0]..proc[1], |reader| {
+                    Procedure::decode((), ENDIANESS, reader)
+                })?;
+            class.procedures.push(proc);
+        }
+        class.redirecting_factories = redirecting_factories;
+        class.index = entry.index.clone();
+        Ok(MaybeDecoded::Decoded(class))
     }
 
-    pub fn canonical_names(&mut self) -> Result<Vec<CanonicalName>, declio::Error> {
-        self.source
-            .seek(SeekFrom::Start(self.index.canonical_names_offset.into()))?;
-        codecs::list::decode((), ENDIANESS, &mut self.source)
+    /// Decodes a class or procedure of a library, keeping its bytes when decoding fails.
+    fn decode_or_keep<A, F>(
+        &mut self,
+        library: usize,
+        library_range: Range<u32>,
+        range: Range<u32>,
+        decode: F,
+    ) -> Result<MaybeDecoded<A>, Error>
+    where
+        F: FnOnce(&mut PositionReader<&mut R>) -> Result<A, declio::Error>,
+    {
+        match self.decode_at(range.start, decode) {
+            Ok(node) => Ok(MaybeDecoded::Decoded(node)),
+            Err(err) => {
+                let error = self.library_path(err, library, library_range);
+                let len = range.end.checked_sub(range.start).ok_or_else(|| {
+                    declio::Error::new(format_args!("Invalid node offset {}", range.start))
+                })?;
+                self.seek(range.start)?;
+                let bytes = codecs::bytes::decode(Len(len as usize), ENDIANESS, &mut self.source)?;
+                Ok(MaybeDecoded::Undecodable(Undecodable {
+                    offset: range.start,
+                    len,
+                    message: error.inner().to_string(),
+                    path: error.path().to_vec(),
+                    bytes,
+                }))
+            }
+        }
+    }
+
+    /// Attaches the library, class and procedure in which an error occurred, located through the
+    /// offset tables of the library.
+    fn library_path(&mut self, err: Error, index: usize, range: Range<u32>) -> Error {
+        let mut path = vec![PathSegment::Library(index)];
+        let offset = self.failing_byte(err.offset());
+        if let (Some(offset), Ok(entry)) = (offset, self.library_entry(range)) {
+            let class = entry
+                .classes
+                .iter()
+                .find(|class| class.range.contains(&offset));
+            let proc = entry
+                .procedures
+                .iter()
+                .find(|proc| proc.range.contains(&offset));
+            if let Some(class) = class {
+                path.extend(self.class_path(class, err.offset()));
+            } else if let Some(proc) = proc {
+                path.push(PathSegment::Procedure(self.name_of(proc.name)));
+            }
+        }
+        err.within(path)
+    }
+
+    fn class_path(&mut self, class: &ClassEntry, offset: Option<u64>) -> Vec<PathSegment> {
+        let mut path = vec![PathSegment::Class(self.name_of(class.name))];
+        if let Some(offset) = self.failing_byte(offset) {
+            let proc = class
+                .procedures
+                .iter()
+                .find(|proc| proc.range.contains(&offset));
+            if let Some(proc) = proc {
+                path.push(PathSegment::Procedure(self.name_of(proc.name)));
+            }
+        }
+        path
+    }
+
+    fn library_entry(&mut self, range: Range<u32>) -> Result<LibraryEntry, Error> {
+        self.seek(range.start)?;
+        let header = LibraryHeader::decode((), ENDIANESS, &mut self.source)?;
+
+        let mut end = range.end;
+        let procedure_offsets = self.read_offsets_before(&mut end)?;
+        let class_offsets = self.read_offsets_before(&mut end)?;
+        let source_refs_offset = self.read_u32_before(&mut end)?;
+        let index = LibraryIndex {
+            source_refs_offset,
+            class_offsets,
+            procedure_offsets,
+        };
+
+        let classes = index
+            .class_offsets
+            .windows(2)
+            .map(|range| self.class_entry(range[0]..range[1]))
+            .collect::<Result<_, _>>()?;
+        let procedures = index
+            .procedure_offsets
+            .windows(2)
+            .map(|range| self.procedure_entry(range[0]..range[1]))
+            .collect::<Result<_, _>>()?;
+
+        Ok(LibraryEntry {
+            canonical_name: header.canonical_name,
+            name: header.name,
+            range,
+            index,
+            classes,
+            procedures,
+        })
+    }
+
+    fn class_entry(&mut self, range: Range<u32>) -> Result<ClassEntry, Error> {
+        self.seek(range.start)?;
+        let header = ClassHeader::decode(self.version(), ENDIANESS, &mut self.source)?;
+
+        let mut end = range.end;
+        let index = ClassIndex {
+            procedure_offsets: self.read_offsets_before(&mut end)?,
+        };
+        let procedures = index
+            .procedure_offsets
+            .windows(2)
+            .map(|range| self.procedure_entry(range[0]..range[1]))
+            .collect::<Result<_, _>>()?;
+
+        Ok(ClassEntry {
+            canonical_name: header.canonical_name,
+            name: header.name,
+            range,
+            index,
+            procedures,
+        })
     }
 
-    pub fn source_map(&mut self) -> Result<Vec<SourceInfo>, declio::Error> {
+    fn procedure_entry(&mut self, range: Range<u32>) -> Result<ProcedureEntry, Error> {
+        self.seek(range.start)?;
+        let header = ProcedureHeader::decode((), ENDIANESS, &mut self.source)?;
+
+        Ok(ProcedureEntry {
+            canonical_name: header.canonical_name,
+            name: header.name,
+            kind: header.kind,
+            range,
+        })
+    }
+
+    pub fn string_table(&mut self) -> Result<StringTable, Error> {
+        self.decode_section("string table", self.index.string_table_offset, |reader| {
+            StringTable::decode((), ENDIANESS, reader)
+        })
+    }
+
+    pub fn constants(&mut self) -> Result<Vec<Constant>, Error> {
+        self.decode_section("constants", self.index.constant_table_offset, |reader| {
+            codecs::list::decode((), ENDIANESS, reader)
+        })
+    }
+
+    pub fn canonical_names(&mut self) -> Result<Vec<CanonicalName>, Error> {
+        self.decode_section(
+            "canonical names",
+            self.index.canonical_names_offset,
+            |reader| codecs::list::decode((), ENDIANESS, reader),
+        )
+    }
+
+    pub fn source_map(&mut self) -> Result<Vec<SourceInfo>, Error> {
+        self.decode_section("source map", self.index.source_table_offset, |reader| {
+            let length = u32::decode((), ENDIANESS, reader)?;
+            <Vec<SourceInfo>>::decode(Len(length as usize), ENDIANESS, reader)
+        })
+    }
+
+    pub fn problems(&mut self) -> Result<Vec<Utf8>, Error> {
+        self.decode_section("problems", ComponentHeader::SIZE as u32, |reader| {
+            codecs::list::decode((), ENDIANESS, reader)
+        })
+    }
+
+    /// Reads the string table, keeping strings with lone surrogates as WTF-8 bytes.
+    pub fn strings(&mut self) -> Result<Vec<StrRef>, Error> {
+        let table = self.string_table()?;
+        let strings = (0..table.len())
+            .filter_map(|i| table.get_bytes(i))
+            .map(|bytes| StrRef::from_wtf8(bytes.to_vec()))
+            .collect();
+        Ok(strings)
+    }
+
+    /// Reads the raw metadata mappings: the tag of every repository along with its
+    /// node offset to payload offset pairs.
+    pub fn metadata_mappings(&mut self) -> Result<Vec<MetadataMapping>, Error> {
+        let mut end = self.index.string_table_offset;
+        let count = self.read_u32_before(&mut end)?;
+
+        let mut mappings = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = self.read_u32_before(&mut end)?;
+            let start = len
+                .checked_mul(8)
+                .and_then(|size| end.checked_sub(size))
+                .ok_or_else(|| declio::Error::new("Invalid metadata mapping length"))?;
+            self.seek(start)?;
+            let mut entries = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                let node_offset = u32::decode((), ENDIANESS, &mut self.source)?;
+                let payload_offset = u32::decode((), ENDIANESS, &mut self.source)?;
+                entries.push((node_offset, payload_offset));
+            }
+            end = start;
+            let tag = self.read_u32_before(&mut end)?;
+            mappings.push(MetadataMapping {
+                tag: ComponentRef::new(Uint(tag)),
+                entries,
+            });
+        }
+        mappings.reverse();
+        Ok(mappings)
+    }
+
+    /// Reads all metadata repositories with their payloads. Payloads are attached to
+    /// [`MetadataTarget::Offset`]s, which stay valid as long as the layout of the component
+    /// does not change. Repositories are decoded once and cached.
+    pub fn metadata(&mut self) -> Result<Vec<MetadataRepository>, Error> {
+        let repos = self.cached_metadata()?;
+        Ok(repos.iter().map(|(_, repo)| repo.clone()).collect())
+    }
+
+    /// Reads the metadata repository with the given tag, e.g. [`crate::metadata::INFERRED_TYPE`].
+    pub fn metadata_repository(&mut self, tag: &str) -> Result<Option<&MetadataRepository>, Error> {
+        let repos = self.cached_metadata()?;
+        let repo = repos
+            .iter()
+            .find(|(name, _)| name.as_deref() == Some(tag))
+            .map(|(_, repo)| repo);
+        Ok(repo)
+    }
+
+    /// Decodes the repository of one of the well-known metadata kinds, keyed by the offset of the
+    /// node each payload annotates.
+    pub fn typed_metadata<M: Metadata>(&mut self) -> Result<BTreeMap<u32, M>, Error> {
+        match self.metadata_repository(M::TAG)? {
+            Some(repo) => Ok(repo.decode_typed()?),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    fn cached_metadata(&mut self) -> Result<&[(Option<String>, MetadataRepository)], Error> {
+        if self.metadata.is_none() {
+            let strings = self.string_table()?;
+            let repos = self
+                .decode_metadata()?
+                .into_iter()
+                .map(|repo| {
+                    let index: u32 = repo.tag.into();
+                    let name = strings.get(index as usize).and_then(Wtf8::as_str);
+                    (name.map(str::to_owned), repo)
+                })
+                .collect();
+            self.metadata = Some(repos);
+        }
+        Ok(self.metadata.as_deref().unwrap_or_default())
+    }
+
+    fn decode_metadata(&mut self) -> Result<Vec<MetadataRepository>, Error> {
+        let mappings = self.metadata_mappings()?;
+
+        let payloads_len =
+            self.index.metadata_mappings_offset - self.index.metadata_payloads_offset;
+        let mut payload_offsets: Vec<u32> = mappings
+            .iter()
+            .flat_map(|mapping| mapping.entries.iter().map(|(_, payload)| *payload))
+            .collect();
+        payload_offsets.push(payloads_len);
+        payload_offsets.sort_unstable();
+        payload_offsets.dedup();
+
+        let mut repos = Vec::with_capacity(mappings.len());
+        for mapping in mappings {
+            let mut repo = MetadataRepository::new(mapping.tag);
+            for (node_offset, payload_offset) in mapping.entries {
+                let next = payload_offsets
+                    .iter()
+                    .find(|offset| **offset > payload_offset)
+                    .ok_or_else(|| {
+                        declio::Error::new(format_args!(
+                            "Invalid metadata payload offset {payload_offset}"
+                        ))
+                    })?;
+                self.seek(self.index.metadata_payloads_offset + payload_offset)?;
+                let len = Len((next - payload_offset) as usize);
+                let payload = codecs::bytes::decode(len, ENDIANESS, &mut self.source)?;
+                repo.entries.push(MetadataEntry {
+                    node: MetadataTarget::Offset(node_offset),
+                    payload,
+                    payload_offset: Some(payload_offset),
+                });
+            }
+            repos.push(repo);
+        }
+        Ok(repos)
+    }
+
+    #[inline]
+    pub fn main_method(&self) -> CanonicalNameRef {
+        ComponentRef::new(Uint(self.index.main_method_reference))
+    }
+
+    #[inline]
+    pub fn non_nullable_mode(&self) -> NonNullableMode {
+        self.index.compilation_mode
+    }
+
+    /// Decodes the whole component into an owned [`Component`] that can be written back with
+    /// [`Component::encode`] using the same [`ComponentHeader`].
+    pub fn into_component(mut self) -> Result<Component, Error> {
+        self.cached_metadata()?;
+        let metadata = self.metadata.take().unwrap_or_default();
+        let comp = Component {
+            problems: self.problems()?,
+            libraries: self.libraries()?,
+            source_map: self.source_map()?,
+            constants: self.constants()?,
+            canonical_names: self.canonical_names()?,
+            metadata: metadata.into_iter().map(|(_, repo)| repo).collect(),
+            strings: self.strings()?,
+            main_method: self.main_method(),
+            non_nullable_mode: self.non_nullable_mode(),
+        };
+        Ok(comp)
+    }
+
+    fn read_u32_before(&mut self, end: &mut u32) -> Result<u32, Error> {
+        *end = end
+            .checked_sub(4)
+            .ok_or_else(|| declio::Error::new("Unexpected start of component"))?;
+        self.seek(*end)?;
+        Ok(u32::decode((), ENDIANESS, &mut self.source)?)
+    }
+
+    /// Reads an offset table laid out as `count + 1` offsets followed by the count.
+    fn read_offsets_before(&mut self, end: &mut u32) -> Result<Vec<u32>, Error> {
+        let count = self.read_u32_before(end)?;
+        *end = count
+            .checked_add(1)
+            .and_then(|len| len.checked_mul(4))
+            .and_then(|size| end.checked_sub(size))
+            .ok_or_else(|| declio::Error::new(format_args!("Invalid offset table size {count}")))?;
+        self.seek(*end)?;
+        Ok(codecs::offsets::decode(
+            count as usize,
+            ENDIANESS,
+            &mut self.source,
+        )?)
+    }
+
+    /// Decodes a node at an offset relative to the start of this component, recording the stream
+    /// position at which decoding failed and the nodes the error propagated out of.
+    fn decode_at<A, F>(&mut self, offset: u32, decode: F) -> Result<A, Error>
+    where
+        F: FnOnce(&mut PositionReader<&mut R>) -> Result<A, declio::Error>,
+    {
+        self.seek(offset)?;
+        let start = self.range.start + u64::from(offset);
+        let mut reader = PositionReader::new(&mut self.source, start);
+        take_node_path();
+        decode(&mut reader).map_err(|err| Error::from(err).at(reader.position()).with_node_path())
+    }
+
+    fn decode_section<A, F>(
+        &mut self,
+        section: &'static str,
+        offset: u32,
+        decode: F,
+    ) -> Result<A, Error>
+    where
+        F: FnOnce(&mut PositionReader<&mut R>) -> Result<A, declio::Error>,
+    {
+        self.decode_at(offset, decode)
+            .map_err(|err| err.within([PathSegment::Section(section)]))
+    }
+
+    /// Offset of the last byte read before an error, relative to the start of this component.
+    fn failing_byte(&self, offset: Option<u64>) -> Option<u32> {
+        let offset = offset?.checked_sub(self.range.start)?.checked_sub(1)?;
+        u32::try_from(offset).ok()
+    }
+
+    /// Resolves a name for error reporting, falling back to its index.
+    fn name_of(&mut self, name: StringRef) -> String {
+        let index: u32 = name.into();
+        self.string_table()
+            .ok()
+            .and_then(|table| Some(table.get(index as usize)?.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| format!("#{index}"))
+    }
+
+    /// Seeks to an offset relative to the start of this component.
+    #[inline]
+    fn seek(&mut self, offset: u32) -> Result<(), Error> {
         self.source
-            .seek(SeekFrom::Start(self.index.source_table_offset.into()))?;
-        let length = u32::decode((), ENDIANESS, &mut self.source)?;
-        <Vec<SourceInfo>>::decode(Len(length as usize), ENDIANESS, &mut self.source)
+            .seek(SeekFrom::Start(self.range.start + u64::from(offset)))?;
+        Ok(())
+    }
+}
+
+/// Options for writing a component with [`Component::encode_with`].
+#[derive(Debug, Default, Clone)]
+pub struct EncodeOptions {
+    /// Writes patterns and the nodes holding them with tags specific to this crate, see
+    /// [`Pattern`](crate::expr::Pattern). Only this crate can read such a component back.
+    pub crate_specific_tags: bool,
+}
+
+/// Checks applied to the component header before anything else is decoded.
+#[derive(Debug, Default, Clone)]
+pub struct LoadOptions {
+    /// Rejects components whose format version differs from this one.
+    pub expected_version: Option<Version>,
+    /// Rejects components built by a different SDK. The VM's wildcard hash
+    /// ([`ComponentHeader::UNKNOWN_SDK_HASH`]) on either side always matches.
+    pub expected_sdk_hash: Option<[u8; 10]>,
+    /// Keeps classes and procedures that fail to decode as [`Undecodable`] raw bytes instead of
+    /// failing the whole library.
+    pub lenient: bool,
+}
+
+impl LoadOptions {
+    pub fn strict(header: &ComponentHeader) -> Self {
+        Self {
+            expected_version: Some(header.format_version()),
+            expected_sdk_hash: Some(header.sdk_hash),
+            lenient: false,
+        }
+    }
+
+    fn check_header(&self, header: &ComponentHeader) -> Result<(), declio::Error> {
+        let version = header.format_version().check_supported()?;
+        match self.expected_version {
+            Some(expected) if expected != version => {
+                return Err(declio::Error::new(format_args!(
+                    "Expected kernel format version {}, found {}",
+                    expected.0, version.0
+                )));
+            }
+            _ => {}
+        }
+        match self.expected_sdk_hash {
+            Some(expected)
+                if expected != header.sdk_hash
+                    && expected != ComponentHeader::UNKNOWN_SDK_HASH
+                    && header.sdk_hash != ComponentHeader::UNKNOWN_SDK_HASH =>
+            {
+                Err(declio::Error::new(format_args!(
+                    "Expected SDK hash {}, found {}",
+                    String::from_utf8_lossy(&expected),
+                    String::from_utf8_lossy(&header.sdk_hash)
+                )))
+            }
+            _ => Ok(()),
+        }
     }
 }
 
@@ -83,23 +905,46 @@ pub struct Component {
     pub source_map: Vec<SourceInfo>,
     pub constants: Vec<Constant>,
     pub canonical_names: Vec<CanonicalName>,
-    pub payloads: Vec<Vec<u8>>,
+    pub metadata: Vec<MetadataRepository>,
     pub strings: Vec<StrRef>,
     pub main_method: CanonicalNameRef,
     pub non_nullable_mode: NonNullableMode,
 }
 
 impl Component {
-    pub fn encode<W: Write + Seek>(
+    /// Finds a redirecting factory by its canonical name, e.g. the target of
+    /// [`Expr::RedirectingFactoryTearOff`] or [`Constant::RedirectingFactoryTearOff`].
+    pub fn redirecting_factory(&self, name: CanonicalNameRef) -> Option<&RedirectingFactory> {
+        self.libraries
+            .iter()
+            .flat_map(|lib| lib.classes.iter().filter_map(MaybeDecoded::decoded))
+            .flat_map(|class| class.redirecting_factories.iter())
+            .find(|factory| factory.canonical_name == name)
+    }
+
+    /// Writes the component without seeking, so any sink works, including one that already holds
+    /// other components. Fails on patterns and the other nodes tagged specifically by this crate
+    /// unless opted into with [`Component::encode_with`]. Returns the offsets at which expressions
+    /// were written.
+    pub fn encode<W: Write>(
         &self,
         header: &ComponentHeader,
         out: &mut W,
-    ) -> Result<(), declio::Error> {
-        writer::write_component(header, self, out)
+    ) -> Result<ExprOffsets, declio::Error> {
+        self.encode_with(header, &EncodeOptions::default(), out)
+    }
+
+    pub fn encode_with<W: Write>(
+        &self,
+        header: &ComponentHeader,
+        opts: &EncodeOptions,
+        out: &mut W,
+    ) -> Result<ExprOffsets, declio::Error> {
+        writer::write_component(header, self, opts, out)
     }
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode)]
 pub struct ComponentHeader {
     pub magic: HeaderMagic,
     pub version: u32,
@@ -107,6 +952,25 @@ pub struct ComponentHeader {
 }
 
 impl ComponentHeader {
+    pub const SIZE: usize = 18;
+
+    /// SDK hash accepted by the VM regardless of the SDK it was built from.
+    pub const UNKNOWN_SDK_HASH: [u8; 10] = *b"0000000000";
+
+    /// Creates a header for an arbitrary format version without pinning an SDK.
+    pub fn with_version(version: Version) -> Self {
+        ComponentHeader {
+            magic: HeaderMagic,
+            version: version.0,
+            sdk_hash: Self::UNKNOWN_SDK_HASH,
+        }
+    }
+
+    #[inline]
+    pub fn format_version(&self) -> Version {
+        Version(self.version)
+    }
+
     pub const DART_2_15_1: ComponentHeader = ComponentHeader {
         magic: HeaderMagic,
         version: 74,
@@ -126,7 +990,7 @@ impl ComponentHeader {
     };
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, Clone, Encode, Decode)]
 #[declio(ctx = "library_count: u32")]
 pub(crate) struct ComponentIndex {
     pub source_table_offset: u32,
@@ -147,6 +1011,14 @@ impl ComponentIndex {
     pub const MINIMUM_SIZE: usize = 44;
 }
 
+/// Metadata mapping as stored in the component, with payload offsets relative to the start of
+/// the metadata payloads section.
+#[derive(Debug, Clone)]
+pub struct MetadataMapping {
+    pub tag: StringRef,
+    pub entries: Vec<(u32, u32)>,
+}
+
 #[derive(Debug, Encode, Decode)]
 pub(crate) struct ComponentMetadata {
     pub library_count: u32,
@@ -155,9 +1027,18 @@ pub(crate) struct ComponentMetadata {
 
 impl ComponentMetadata {
     pub const SIZE: usize = 8;
+
+    fn read_ending_at<R: Read + Seek>(source: &mut R, end: u64) -> Result<Self, declio::Error> {
+        let start = end.checked_sub(Self::SIZE as u64).ok_or_else(|| {
+            declio::Error::new(format_args!("Truncated component metadata at offset {end}"))
+        })?;
+        source.seek(SeekFrom::Start(start))?;
+        Self::decode((), ENDIANESS, source)
+    }
 }
 
-#[derive(Debug, Default, Decode)]
+#[derive(Debug, Default, PartialEq, Decode)]
+#[declio(ctx = "version: Version")]
 pub struct Library {
     pub flags: LibraryFlags,
     pub version_major: Uint,
@@ -175,21 +1056,190 @@ pub struct Library {
     pub additional_exports: Vec<CanonicalNameRef>,
     #[declio(with = "codecs::list")]
     pub library_parts: Vec<LibraryPart>,
-    #[declio(with = "codecs::list")]
+    #[declio(with = "codecs::members")]
     pub typedefs: Vec<Typedef>,
-    #[declio(with = "codecs::list")]
-    pub classes: Vec<Class>,
-    #[declio(with = "codecs::list")]
+    #[declio(with = "codecs::list", ctx = "version")]
+    pub classes: Vec<MaybeDecoded<Class>>,
+    #[declio(with = "codecs::members", ctx = "version")]
     pub extensions: Vec<Extension>,
-    #[declio(with = "codecs::list")]
+    #[declio(
+        with = "codecs::members",
+        skip_if = "version < Version::EXTENSION_TYPES"
+    )]
+    pub extension_types: Vec<ExtensionTypeDeclaration>,
+    #[declio(with = "codecs::members")]
     pub fields: Vec<Field>,
     #[declio(with = "codecs::list")]
-    pub procedures: Vec<Procedure>,
+    pub procedures: Vec<MaybeDecoded<Procedure>>,
     #[declio(with = "codecs::list")]
     pub source_refs: Vec<Uint>,
+    #[declio(ctx = "(classes.len(), procedures.len())")]
+    pub index: LibraryIndex,
+}
+
+/// Class or procedure, which is only left undecoded when loading with
+/// [`LoadOptions::lenient`].
+#[derive(Debug, PartialEq)]
+pub enum MaybeDecoded<A> {
+    Decoded(A),
+    Undecodable(Undecodable),
+}
+
+impl<A> MaybeDecoded<A> {
+    #[inline]
+    pub fn decoded(&self) -> Option<&A> {
+        match self {
+            MaybeDecoded::Decoded(node) => Some(node),
+            MaybeDecoded::Undecodable(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn decoded_mut(&mut self) -> Option<&mut A> {
+        match self {
+            MaybeDecoded::Decoded(node) => Some(node),
+            MaybeDecoded::Undecodable(_) => None,
+        }
+    }
+}
+
+impl<A> From<A> for MaybeDecoded<A> {
+    fn from(node: A) -> Self {
+        MaybeDecoded::Decoded(node)
+    }
+}
+
+impl<A: Decode<C>, C> Decode<C> for MaybeDecoded<A> {
+    fn decode<R: Read>(ctx: C, endian: Endian, reader: &mut R) -> Result<Self, declio::Error> {
+        A::decode(ctx, endian, reader).map(MaybeDecoded::Decoded)
+    }
+}
+
+/// Raw bytes of a node that failed to decode. They are written back verbatim, except for the
+/// procedure offsets trailing a class which are moved along with it.
+#[derive(Debug)]
+pub struct Undecodable {
+    /// Offset of the node relative to the start of the component it was read from.
+    pub offset: u32,
+    pub len: u32,
+    /// Message of the [`Error`] the node failed with, kept as a string so that components stay
+    /// `Send` and `Sync`.
+    pub message: String,
+    /// Path of the [`Error`] the node failed with.
+    pub path: Vec<PathSegment>,
+    pub bytes: Vec<u8>,
+}
+
+/// Errors are not compared, nodes kept from the same bytes are equal.
+impl PartialEq for Undecodable {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset && self.len == other.len && self.bytes == other.bytes
+    }
+}
+
+/// Offset tables trailing a library. Offsets are relative to the start of the component and
+/// include the end offset of the last class and procedure.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LibraryIndex {
+    pub source_refs_offset: u32,
+    pub class_offsets: Vec<u32>,
+    pub procedure_offsets: Vec<u32>,
+}
+
+impl Decode<(usize, usize)> for LibraryIndex {
+    fn decode<R>(
+        (class_count, proc_count): (usize, usize),
+        endian: Endian,
+        reader: &mut R,
+    ) -> Result<Self, declio::Error>
+    where
+        R: Read,
+    {
+        let source_refs_offset = u32::decode((), endian, reader)?;
+        let class_offsets = codecs::offsets::decode(class_count, endian, reader)?;
+        let procedure_offsets = codecs::offsets::decode(proc_count, endian, reader)?;
+        Ok(LibraryIndex {
+            source_refs_offset,
+            class_offsets,
+            procedure_offsets,
+        })
+    }
+}
+
+/// Last offset of an offset table, at which the node following its classes or procedures starts.
+/// Tables list the end offset of their last entry, so only a corrupted one can be empty.
+fn end_offset(table: &str, offsets: &[u32]) -> Result<u32, declio::Error> {
+    offsets
+        .last()
+        .copied()
+        .ok_or_else(|| declio::Error::new(format_args!("Empty {table} offset table")))
+}
+
+/// A library listed from the offset tables of a component.
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    pub canonical_name: CanonicalNameRef,
+    pub name: StringRef,
+    pub range: Range<u32>,
+    pub index: LibraryIndex,
+    pub classes: Vec<ClassEntry>,
+    pub procedures: Vec<ProcedureEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassEntry {
+    pub canonical_name: CanonicalNameRef,
+    pub name: StringRef,
+    pub range: Range<u32>,
+    pub index: ClassIndex,
+    pub procedures: Vec<ProcedureEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcedureEntry {
+    pub canonical_name: CanonicalNameRef,
+    pub name: StringRef,
+    pub kind: ProcedureKind,
+    pub range: Range<u32>,
+}
+
+/// Leading fields of a library, enough to identify it.
+#[derive(Debug, Decode)]
+struct LibraryHeader {
+    _flags: LibraryFlags,
+    _version_major: Uint,
+    _version_minor: Uint,
+    canonical_name: CanonicalNameRef,
+    name: StringRef,
+}
+
+#[derive(Debug, Decode)]
+#[declio(ctx = "version: Version")]
+struct ClassHeader {
+    _tag: ClassTag,
+    canonical_name: CanonicalNameRef,
+    _file_uri: UriRef,
+    _start_offset: FileOffset,
+    _definition_range: FileRange,
+    #[declio(ctx = "version")]
+    _flags: ClassFlags,
+    name: StringRef,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Decode)]
+struct ProcedureHeader {
+    _tag: ProcedureTag,
+    canonical_name: CanonicalNameRef,
+    _file_uri: UriRef,
+    _offset: FileOffset,
+    _definition_range: FileRange,
+    kind: ProcedureKind,
+    _stub_kind: ProcedureStubKind,
+    _flags: ProcedureFlags,
+    name: StringRef,
+}
+
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct LibraryDependency {
     pub offset: FileOffset,
     pub flags: DependencyFlags,
@@ -201,7 +1251,7 @@ pub struct LibraryDependency {
     pub combinators: Vec<Combinator>,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct LibraryPart {
     #[declio(with = "codecs::list")]
     pub annotations: Vec<Expr>,
@@ -220,12 +1270,16 @@ pub struct StringTable {
 }
 
 impl StringTable {
-    pub fn new<'a, T: IntoIterator<Item = &'a str>>(strs: T) -> Result<Self, declio::Error> {
+    pub fn new<A, T>(strs: T) -> Result<Self, declio::Error>
+    where
+        A: AsRef<[u8]>,
+        T: IntoIterator<Item = A>,
+    {
         let mut end_offsets = vec![];
         let mut bytes = Cursor::new(vec![]);
 
         for str in strs {
-            bytes.write_all(str.as_bytes())?;
+            bytes.write_all(str.as_ref())?;
             end_offsets.push((bytes.position() as u32).into());
         }
         let res = StringTable {
@@ -235,30 +1289,89 @@ impl StringTable {
         Ok(res)
     }
 
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end_offsets.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.end_offsets.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Wtf8> {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+
+    #[inline]
     pub fn get(&self, index: usize) -> Option<&Wtf8> {
-        let start = if index == 0 {
-            0usize
-        } else {
-            self.end_offsets.get(index - 1)?.0 as usize
-        };
-        let end = self.end_offsets.get(index)?.0 as usize;
-        let bytes = &self.bytes[start..end];
-        Some(Wtf8::from_bytes(bytes))
+        string_at(&self.end_offsets, &self.bytes, index).map(Wtf8::from_bytes)
+    }
+
+    /// Returns the raw WTF-8 bytes of a string.
+    #[inline]
+    pub fn get_bytes(&self, index: usize) -> Option<&[u8]> {
+        string_at(&self.end_offsets, &self.bytes, index)
     }
 }
 
 impl fmt::Debug for StringTable {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut list = f.debug_list();
-        for i in 0..self.end_offsets.len() {
-            let elem = self.get(i).unwrap();
-            list.entry(&elem);
-        }
-        list.finish()
+        f.debug_list().entries(self.iter()).finish()
     }
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+/// String table borrowing its contents from the underlying buffer.
+pub struct StringTableRef<'a> {
+    end_offsets: Vec<Uint>,
+    bytes: &'a [u8],
+}
+
+impl<'a> StringTableRef<'a> {
+    fn decode(reader: &mut Cursor<&'a [u8]>) -> Result<Self, declio::Error> {
+        let end_offsets: Vec<Uint> = codecs::list::decode((), ENDIANESS, reader)?;
+        let len = end_offsets.last().map(|i| i.0 as usize).unwrap_or(0);
+        let bytes = read_slice(reader, len)?;
+        Ok(Self { end_offsets, bytes })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end_offsets.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.end_offsets.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a Wtf8> + '_ {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&'a Wtf8> {
+        string_at(&self.end_offsets, self.bytes, index).map(Wtf8::from_bytes)
+    }
+}
+
+impl fmt::Debug for StringTableRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+fn string_at<'a>(end_offsets: &[Uint], bytes: &'a [u8], index: usize) -> Option<&'a [u8]> {
+    let start = if index == 0 {
+        0usize
+    } else {
+        end_offsets.get(index - 1)?.0 as usize
+    };
+    let end = end_offsets.get(index)?.0 as usize;
+    bytes.get(start..end)
+}
+
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct SourceInfo {
     pub uri: Utf8,
     pub source: Utf8,
@@ -269,6 +1382,45 @@ pub struct SourceInfo {
     pub constructor_coverage: Vec<CanonicalNameRef>,
 }
 
+/// Source info borrowing its text from the underlying buffer.
+#[derive(Debug)]
+pub struct SourceInfoRef<'a> {
+    pub uri: &'a str,
+    pub source: &'a str,
+    pub line_starts: Vec<Uint>,
+    pub import_uri: &'a str,
+    pub constructor_coverage: Vec<CanonicalNameRef>,
+}
+
+impl<'a> SourceInfoRef<'a> {
+    fn decode(reader: &mut Cursor<&'a [u8]>) -> Result<Self, declio::Error> {
+        Ok(Self {
+            uri: read_utf8(reader)?,
+            source: read_utf8(reader)?,
+            line_starts: codecs::list::decode((), ENDIANESS, reader)?,
+            import_uri: read_utf8(reader)?,
+            constructor_coverage: codecs::list::decode((), ENDIANESS, reader)?,
+        })
+    }
+}
+
+fn read_slice<'a>(reader: &mut Cursor<&'a [u8]>, len: usize) -> Result<&'a [u8], declio::Error> {
+    let bytes: &'a [u8] = reader.get_ref();
+    let start = reader.position() as usize;
+    let slice = start
+        .checked_add(len)
+        .and_then(|end| bytes.get(start..end))
+        .ok_or_else(|| declio::Error::new("Unexpected end of input"))?;
+    reader.set_position((start + len) as u64);
+    Ok(slice)
+}
+
+fn read_utf8<'a>(reader: &mut Cursor<&'a [u8]>) -> Result<&'a str, declio::Error> {
+    let len = Uint::decode((), ENDIANESS, reader)?;
+    std::str::from_utf8(read_slice(reader, len.0 as usize)?)
+        .map_err(|err| declio::Error::new(format_args!("Invalid UTF-8 string: {err}")))
+}
+
 #[derive(Debug, Clone, Copy, Encode, Decode)]
 #[declio(id_type = "u32")]
 pub enum NonNullableMode {
@@ -287,3 +1439,368 @@ impl Default for NonNullableMode {
         Self::Weak
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::fs;
+
+    use super::*;
+    use crate::flags::{MemberDescriptorFlags, RedirectingFactoryFlags};
+    use crate::node::{ExtensionMemberDescriptor, ExtensionMemberKind, ExtensionShowHideClause};
+    use crate::testing::{sample_builder, sample_bytes, sample_component, write_and_load};
+
+    #[test]
+    fn test_borrowed_reads() -> Result<(), Box<dyn Error>> {
+        let mut comp = Component::default();
+        comp.strings.push(StrRef::Borrowed("test😵"));
+        comp.source_map.push(SourceInfo {
+            uri: Utf8("file:///main.dart".to_owned()),
+            source: Utf8("void main() {}".to_owned()),
+            ..SourceInfo::default()
+        });
+
+        let (bytes, loaded) = write_and_load(&comp)?;
+        assert_eq!(loaded.bytes(), bytes.as_slice());
+
+        let strings = loaded.string_table_ref()?;
+        assert_eq!(strings.get(0).and_then(|wtf| wtf.as_str()), Some("test😵"));
+
+        let sources = loaded.source_map_ref()?;
+        assert_eq!(sources[0].uri, "file:///main.dart");
+        assert_eq!(sources[0].source, "void main() {}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_libraries() -> Result<(), Box<dyn Error>> {
+        let (bytes, corrupted) = sample_bytes()?;
+        for (bytes, lenient) in [(&bytes, false), (&corrupted, true)] {
+            let opts = LoadOptions {
+                lenient,
+                ..LoadOptions::default()
+            };
+            let mut loaded = ComponentFile::load_with(Cursor::new(bytes.as_slice()), &opts)?;
+            let libs = loaded.libraries()?;
+            assert_eq!(loaded.library_at(0)?, libs[0]);
+            assert!(loaded.library_at(1).is_err());
+        }
+
+        let strict = ComponentFile::from_bytes(corrupted.as_slice())?;
+        assert!(strict.library_at(0).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_libraries() -> Result<(), Box<dyn Error>> {
+        let (bytes, corrupted) = sample_bytes()?;
+        for (bytes, lenient) in [(&bytes, false), (&corrupted, true)] {
+            let opts = LoadOptions {
+                lenient,
+                ..LoadOptions::default()
+            };
+            let mut loaded = ComponentFile::load_with(Cursor::new(bytes.as_slice()), &opts)?;
+            assert_eq!(loaded.libraries_par()?, loaded.libraries()?);
+        }
+
+        let strict = ComponentFile::from_bytes(corrupted.as_slice())?;
+        assert!(strict.libraries_par().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let comp = Component::default();
+        let header = ComponentHeader::with_version(Version(Version::MAX_SUPPORTED.0 + 1));
+
+        let mut buf: Cursor<Vec<u8>> = Cursor::new(vec![]);
+        assert!(comp.encode(&header, &mut buf).is_err());
+    }
+
+    #[test]
+    fn test_version_gates() -> Result<(), Box<dyn Error>> {
+        let roundtrip = |version: Version, comp: &Component| -> Result<Component, Box<dyn Error>> {
+            let mut bytes = vec![];
+            comp.encode(&ComponentHeader::with_version(version), &mut bytes)?;
+            let loaded = ComponentFile::load(Cursor::new(bytes.as_slice()))?;
+            assert_eq!(loaded.version(), version);
+            Ok(loaded.into_component()?)
+        };
+
+        // show/hide clauses are dropped from version 76 on
+        let (mut comp, main_name) = sample_component();
+        comp.libraries[0].extensions.push(Extension {
+            show_hide_clause: Some(ExtensionShowHideClause {
+                shown_members: vec![main_name],
+                ..ExtensionShowHideClause::default()
+            }),
+            ..Extension::default()
+        });
+        let before = roundtrip(Version::V75, &comp)?;
+        let clause = before.libraries[0].extensions[0].show_hide_clause.as_ref();
+        assert_eq!(
+            clause.map(|clause| &clause.shown_members[..]),
+            Some(&[main_name][..])
+        );
+        let after = roundtrip(Version::NO_SHOW_HIDE, &comp)?;
+        assert!(after.libraries[0].extensions[0].show_hide_clause.is_none());
+
+        // class flags are widened to a `Uint` from version 90 on
+        let (mut comp, _) = sample_component();
+        let class = comp.libraries[0].classes[0].decoded_mut().unwrap();
+        class.flags = ClassFlags::new().with_is_abstract(true);
+        let before = roundtrip(Version(Version::CLASS_MODIFIERS.0 - 1), &comp)?;
+        let class = before.libraries[0].classes[0].decoded().unwrap();
+        assert!(class.flags.is_abstract());
+
+        let class = comp.libraries[0].classes[0].decoded_mut().unwrap();
+        class.flags = ClassFlags::new().with_is_final(true);
+        assert!(roundtrip(Version(Version::CLASS_MODIFIERS.0 - 1), &comp).is_err());
+        let after = roundtrip(Version::CLASS_MODIFIERS, &comp)?;
+        let class = after.libraries[0].classes[0].decoded().unwrap();
+        assert!(class.flags.is_final() && !class.flags.is_abstract());
+
+        // extension member descriptors reference their tear-offs from version 106 on
+        let (mut comp, main_name) = sample_component();
+        comp.libraries[0].extensions.push(Extension {
+            members: vec![ExtensionMemberDescriptor {
+                name: StringRef::UNDEFINED,
+                kind: ExtensionMemberKind::Method,
+                flags: MemberDescriptorFlags::new(),
+                member: main_name,
+                tear_off: main_name,
+            }],
+            ..Extension::default()
+        });
+        let before = roundtrip(Version(Version::EXTENSION_TYPES.0 - 1), &comp)?;
+        let member = &before.libraries[0].extensions[0].members[0];
+        assert_eq!(member.tear_off, CanonicalNameRef::UNDEFINED);
+        let after = roundtrip(Version::EXTENSION_TYPES, &comp)?;
+        let member = &after.libraries[0].extensions[0].members[0];
+        assert_eq!(member.tear_off, main_name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_validation() -> Result<(), Box<dyn Error>> {
+        let (bytes, _) = write_and_load(&Component::default())?;
+
+        let opts = LoadOptions::strict(&ComponentHeader::DART_2_16_0_134);
+        let loaded = ComponentFile::load_with(Cursor::new(bytes.as_slice()), &opts)?;
+        assert_eq!(
+            loaded.header().sdk_hash,
+            ComponentHeader::DART_2_16_0_134.sdk_hash
+        );
+
+        let opts = LoadOptions::strict(&ComponentHeader::DART_2_17_0_1);
+        assert!(ComponentFile::load_with(Cursor::new(bytes.as_slice()), &opts).is_err());
+
+        let opts = LoadOptions {
+            expected_version: Some(ComponentHeader::DART_2_15_1.format_version()),
+            ..LoadOptions::default()
+        };
+        assert!(ComponentFile::load_with(Cursor::new(bytes.as_slice()), &opts).is_err());
+
+        let mut truncated = bytes;
+        truncated.remove(ComponentHeader::SIZE);
+        assert!(ComponentFile::load(Cursor::new(truncated)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concatenated_components() -> Result<(), Box<dyn Error>> {
+        let mut bytes = vec![];
+        for str in ["first", "second"] {
+            let mut comp = Component::default();
+            comp.strings.push(StrRef::Borrowed(str));
+            comp.encode(&ComponentHeader::DART_2_16_0_134, &mut bytes)?;
+        }
+
+        let source = Cursor::new(bytes.as_slice());
+        assert!(ComponentFile::load(source.clone()).is_err());
+
+        let mut loaded = ComponentFile::load_all(source, &LoadOptions::default())?;
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].range().start, 0);
+        assert_eq!(loaded[0].range().end, loaded[1].range().start);
+        assert_eq!(loaded[1].range().end, bytes.len() as u64);
+
+        let first = loaded[0].string_table()?;
+        assert_eq!(first.get(0).and_then(|wtf| wtf.as_str()), Some("first"));
+        let second = loaded[1].string_table()?;
+        assert_eq!(second.get(0).and_then(|wtf| wtf.as_str()), Some("second"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_component_roundtrip() -> Result<(), Box<dyn Error>> {
+        let (bytes, loaded) = write_and_load(&sample_component().0)?;
+        assert_eq!(bytes.len() % 8, 0);
+
+        let comp = loaded.into_component()?;
+        assert_eq!(comp.libraries.len(), 1);
+        assert_eq!(comp.libraries[0].procedures.len(), 1);
+
+        let (rewritten, _) = write_and_load(&comp)?;
+        assert_eq!(rewritten, bytes);
+
+        Ok(())
+    }
+
+    /// Every dill under `fixtures/` must decode, and writing it back must give a component that
+    /// decodes and writes back to the same bytes.
+    #[test]
+    fn test_fixture_roundtrips() -> Result<(), Box<dyn Error>> {
+        fn rewrite(bytes: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+            let mut rewritten = vec![];
+            for file in ComponentFile::load_all(Cursor::new(bytes), &LoadOptions::default())? {
+                let header = file.header().clone();
+                file.into_component()?.encode(&header, &mut rewritten)?;
+            }
+            Ok(rewritten)
+        }
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() != Some("dill".as_ref()) {
+                continue;
+            }
+            let rewritten = rewrite(&fs::read(&path)?)?;
+            assert!(
+                rewrite(&rewritten)? == rewritten,
+                "{} does not round-trip",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_library_entries() -> Result<(), Box<dyn Error>> {
+        let (comp, main_name) = sample_component();
+        let (_, mut loaded) = write_and_load(&comp)?;
+        let libs = loaded.library_entries()?;
+        assert_eq!(libs.len(), 1);
+        assert_eq!(libs[0].procedures.len(), 1);
+        assert_eq!(libs[0].classes.len(), 1);
+
+        let main = loaded.procedure(&libs[0].procedures[0])?;
+        assert_eq!(main.canonical_name, main_name);
+        assert!(main.function.body.is_some());
+
+        let class = &libs[0].classes[0];
+        assert_eq!(class.procedures.len(), 1);
+        assert!(class.range.start < class.procedures[0].range.start);
+        assert!(class.procedures[0].range.end < class.range.end);
+        let bar = loaded.procedure(&class.procedures[0])?;
+        assert_eq!(bar.name, class.procedures[0].name);
+        assert_eq!(loaded.class(class)?.procedures.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_redirecting_factories() -> Result<(), Box<dyn Error>> {
+        let (mut builder, main_name) = sample_builder();
+        let factory_name = builder.add_name_from([
+            StrRef::Borrowed("sample"),
+            StrRef::Borrowed("Foo"),
+            StrRef::Borrowed("@factories"),
+            StrRef::Borrowed("create"),
+        ]);
+        let target_name = builder.add_name_from([
+            StrRef::Borrowed("sample"),
+            StrRef::Borrowed("Bar"),
+            StrRef::Borrowed("@constructors"),
+            StrRef::Borrowed(""),
+        ]);
+        let mut comp = builder.into_component(main_name, NonNullableMode::Strong);
+        let class = comp.libraries[0].classes[0].decoded_mut().unwrap();
+        class.redirecting_factories.push(RedirectingFactory {
+            canonical_name: factory_name,
+            flags: RedirectingFactoryFlags::new().with_is_const(true),
+            target: target_name,
+            ..RedirectingFactory::default()
+        });
+
+        let comp = write_and_load(&comp)?.1.into_component()?;
+        let factory = comp
+            .redirecting_factory(factory_name)
+            .expect("missing factory");
+        assert_eq!(factory.target, target_name);
+        assert!(factory.flags.is_const());
+        assert!(comp.redirecting_factory(main_name).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_decoding() -> Result<(), Box<dyn Error>> {
+        let (comp, main_name) = sample_component();
+        let (mut bytes, mut loaded) = write_and_load(&comp)?;
+        let entry = loaded.library_entries()?.remove(0);
+        let bar = entry.classes[0].procedures[0].range.clone();
+
+        // the procedure ends with the option tag of its empty body
+        bytes[bar.end as usize - 1] = 0xFF;
+        let opts = LoadOptions {
+            lenient: true,
+            ..LoadOptions::default()
+        };
+        let loaded = ComponentFile::load_with(Cursor::new(bytes.as_slice()), &opts)?;
+        let comp = loaded.into_component()?;
+
+        let lib = &comp.libraries[0];
+        let class = lib.classes[0].decoded().expect("class not decoded");
+        match &class.procedures[0] {
+            MaybeDecoded::Undecodable(proc) => {
+                assert_eq!(proc.offset, bar.start);
+                assert_eq!(proc.len, bar.end - bar.start);
+                assert_eq!(proc.path[1..], [
+                    PathSegment::Class("Foo".to_owned()),
+                    PathSegment::Procedure("bar".to_owned()),
+                    PathSegment::Body
+                ]);
+            }
+            MaybeDecoded::Decoded(_) => panic!("procedure should not be decodable"),
+        }
+        assert_eq!(
+            lib.procedures[0].decoded().map(|proc| proc.canonical_name),
+            Some(main_name)
+        );
+
+        let mut rewritten = vec![];
+        comp.encode(&ComponentHeader::DART_2_16_0_134, &mut rewritten)?;
+        assert_eq!(rewritten, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_decoding_without_classes() -> Result<(), Box<dyn Error>> {
+        let (mut comp, _) = sample_component();
+        comp.libraries[0].classes.clear();
+        comp.libraries[0].fields.push(Field::default());
+        let (bytes, _) = write_and_load(&comp)?;
+
+        let opts = LoadOptions {
+            lenient: true,
+            ..LoadOptions::default()
+        };
+        let loaded = ComponentFile::load_with(Cursor::new(bytes), &opts)?.into_component()?;
+        let lib = &loaded.libraries[0];
+        assert!(lib.classes.is_empty());
+        assert_eq!(lib.fields, comp.libraries[0].fields);
+        assert_eq!(lib.procedures, comp.libraries[0].procedures);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::{fmt, io};
+
+/// Error raised while reading a component, along with where in the stream it happened.
+#[derive(Debug)]
+pub struct Error {
+    inner: declio::Error,
+    offset: Option<u64>,
+    path: Vec<PathSegment>,
+}
+
+impl Error {
+    /// Underlying codec error.
+    #[inline]
+    pub fn inner(&self) -> &declio::Error {
+        &self.inner
+    }
+
+    /// Position in the stream at which decoding failed, when known.
+    #[inline]
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// Nodes that were being decoded when the failure occurred, outermost first.
+    #[inline]
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    pub(crate) fn at(mut self, offset: u64) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    pub(crate) fn within<I: IntoIterator<Item = PathSegment>>(mut self, segments: I) -> Self {
+        self.path.splice(0..0, segments);
+        self
+    }
+
+    /// Appends the segments left by the nodes the error propagated out of, see [`within_node`].
+    pub(crate) fn with_node_path(mut self) -> Self {
+        self.path.extend(take_node_path());
+        self
+    }
+}
+
+impl From<declio::Error> for Error {
+    fn from(inner: declio::Error) -> Self {
+        Self {
+            inner,
+            offset: None,
+            path: vec![],
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        declio::Error::from(err).into()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.inner)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {offset}")?;
+        }
+        for (i, segment) in self.path.iter().enumerate() {
+            f.write_str(if i == 0 { " in " } else { " > " })?;
+            write!(f, "{segment}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+/// Node that was being decoded. Classes and procedures are named after the offset tables listing
+/// them, other members are identified by their index. Within a member, paths go down through the
+/// function body and the statements and expressions holding the failing node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Library(usize),
+    Class(String),
+    Procedure(String),
+    Typedef(usize),
+    Extension(usize),
+    ExtensionType(usize),
+    Field(usize),
+    Constructor(usize),
+    RedirectingFactory(usize),
+    Body,
+    /// Variant of a statement, expression, type or constant, e.g. `Stmt::If`, or only the name
+    /// of the enum when its tag is unknown.
+    Node(&'static str),
+    Section(&'static str),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Library(index) => write!(f, "library[{index}]"),
+            PathSegment::Class(name) => write!(f, r#"class "{name}""#),
+            PathSegment::Procedure(name) => write!(f, r#"procedure "{name}""#),
+            PathSegment::Typedef(index) => write!(f, "typedef[{index}]"),
+            PathSegment::Extension(index) => write!(f, "extension[{index}]"),
+            PathSegment::ExtensionType(index) => write!(f, "extension_type[{index}]"),
+            PathSegment::Field(index) => write!(f, "field[{index}]"),
+            PathSegment::Constructor(index) => write!(f, "constructor[{index}]"),
+            PathSegment::RedirectingFactory(index) => write!(f, "redirecting_factory[{index}]"),
+            PathSegment::Body => f.write_str("body"),
+            PathSegment::Node(name) => f.write_str(name),
+            PathSegment::Section(name) => f.write_str(name),
+        }
+    }
+}
+
+thread_local! {
+    /// Segments of the nodes that the error being returned on this thread propagated out of,
+    /// innermost first. Node codecs only see a generic reader and return a [`declio::Error`], so
+    /// they leave their segments here for [`Error::with_node_path`] to pick up.
+    static NODE_PATH: RefCell<Vec<PathSegment>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records that an error propagated out of a node, when `res` is one.
+pub(crate) fn within_node<A, F>(
+    res: Result<A, declio::Error>,
+    segment: F,
+) -> Result<A, declio::Error>
+where
+    F: FnOnce() -> PathSegment,
+{
+    if res.is_err() {
+        NODE_PATH.with(|path| path.borrow_mut().push(segment()));
+    }
+    res
+}
+
+/// Takes the segments recorded by [`within_node`], outermost first. Errors that were handled
+/// instead of returned leave segments behind, so this is also called before decoding.
+pub(crate) fn take_node_path() -> Vec<PathSegment> {
+    let mut path = NODE_PATH.with(|path| path.take());
+    path.reverse();
+    path
+}
+
+/// Reader that keeps track of its position, used to report where decoding failed.
+pub(crate) struct PositionReader<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R> PositionReader<R> {
+    pub fn new(inner: R, position: u64) -> Self {
+        Self { inner, position }
+    }
+
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+impl<R: io::Read> io::Read for PositionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::component::{ComponentFile, ComponentHeader};
+    use crate::expr::{Expr, Stmt};
+    use crate::node::{Field, RedirectingFactory};
+    use crate::prim::FileOffset;
+    use crate::testing::{sample_component, write_and_load};
+
+    #[test]
+    fn test_decode_error_path() -> Result<(), Box<dyn Error>> {
+        let (mut bytes, mut loaded) = write_and_load(&sample_component().0)?;
+        let entry = loaded.library_entries()?.remove(0);
+        let bar = entry.classes[0].procedures[0].range.clone();
+
+        // the procedure ends with the option tag of its empty body
+        bytes[bar.end as usize - 1] = 0xFF;
+        let mut loaded = ComponentFile::from_bytes(bytes)?;
+
+        let err = loaded.libraries().unwrap_err();
+        assert_eq!(err.path(), [
+            PathSegment::Library(0),
+            PathSegment::Class("Foo".to_owned()),
+            PathSegment::Procedure("bar".to_owned()),
+            PathSegment::Body
+        ]);
+        let offset = err.offset().expect("missing error offset");
+        assert_eq!(offset as u32, bar.end);
+        assert!(err
+            .to_string()
+            .contains(r#"in library[0] > class "Foo" > procedure "bar" > body"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_error_path() -> Result<(), Box<dyn Error>> {
+        let (mut comp, _) = sample_component();
+        let main = comp.libraries[0].procedures[0].decoded_mut().unwrap();
+        let call = main.function.body.take().unwrap();
+        main.function.body = Some(Box::new(Stmt::If {
+            offset: FileOffset::default(),
+            condition: Box::new(Expr::TrueLit),
+            then: call,
+            otherwise: Box::new(Stmt::Empty),
+        }));
+        let mut bytes = vec![];
+        let offsets = comp.encode(&ComponentHeader::DART_2_16_0_134, &mut bytes)?;
+
+        let main = comp.libraries[0].procedures[0].decoded().unwrap();
+        let condition = match main.function.body.as_deref() {
+            Some(Stmt::If { condition, .. }) => offsets.get(condition).unwrap(),
+            _ => panic!("main should consist of an if statement"),
+        };
+        bytes[condition as usize] = 0xFF;
+
+        let err = ComponentFile::from_bytes(bytes)?.libraries().unwrap_err();
+        assert_eq!(err.path()[2..], [
+            PathSegment::Body,
+            PathSegment::Node("Stmt::If"),
+            PathSegment::Node("Expr")
+        ]);
+        assert!(err.to_string().contains("> body > Stmt::If > Expr"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_member_error_paths() -> Result<(), Box<dyn Error>> {
+        let (mut comp, _) = sample_component();
+        comp.libraries[0].fields.push(Field::default());
+        let class = comp.libraries[0].classes[0].decoded_mut().unwrap();
+        class
+            .redirecting_factories
+            .push(RedirectingFactory::default());
+        let (bytes, mut loaded) = write_and_load(&comp)?;
+        let entry = loaded.library_entries()?.remove(0);
+        // fields follow the empty list of extensions, redirecting factories the class procedures
+        let field = *entry.index.class_offsets.last().unwrap() + 2;
+        let factory = *entry.classes[0].index.procedure_offsets.last().unwrap() + 1;
+
+        let cases = [
+            (field, vec![PathSegment::Field(0)]),
+            (factory, vec![
+                PathSegment::Class("Foo".to_owned()),
+                PathSegment::RedirectingFactory(0),
+            ]),
+        ];
+        for (offset, members) in cases {
+            let mut corrupted = bytes.clone();
+            corrupted[offset as usize] = 0xFF;
+            let err = ComponentFile::from_bytes(corrupted)?
+                .libraries()
+                .unwrap_err();
+            assert_eq!(err.path()[1..], members[..]);
+        }
+
+        let mut moved = entry.clone();
+        moved.range.start += 1;
+        assert!(loaded.library(&moved).is_err());
+
+        Ok(())
+    }
+}
@@ -1,539 +1,838 @@
 use declio::{Decode, Encode};
 
 use crate::codecs;
-use crate::flags::{DynamicCastFlags, InvocationFlags, VarDeclFlags};
+use crate::flags::{
+    DynamicCastFlags, InvocationFlags, PatternSwitchCaseFlags, PatternVarDeclFlags, TryCatchFlags, VarDeclFlags, YieldFlags
+};
 use crate::node::{Function, Type, TypeParameter};
 use crate::prim::*;
 
-#[derive(Debug, Encode, Decode)]
+/// First tag specific to this crate, see [`Pattern`]. Expressions and statements tagged from here
+/// up are only written when opted into with [`EncodeOptions`](crate::component::EncodeOptions).
+pub const FIRST_CRATE_TAG: u8 = 152;
+
+codecs::node_enum! {
+    on_encode = crate::writer::record_expr;
+    #[derive(Debug, PartialEq)]
+    pub enum Expr {
+        #[tag = 19]
+        Invalid {
+            offset: FileOffset,
+            message: StringRef,
+            #[declio(with = "codecs::option")]
+            expression: Option<Box<Expr>>,
+        },
+        #[tag = 20]
+        VarGet {
+            offset: FileOffset,
+            var_decl_position: Uint,
+            var: VarRef,
+            #[declio(with = "codecs::option")]
+            promoted_type: Option<Box<Type>>,
+        },
+        #[tag = 128]
+        SpecializedVarGet0(FileOffset, Uint),
+        #[tag = 129]
+        SpecializedVarGet1(FileOffset, Uint),
+        #[tag = 130]
+        SpecializedVarGet2(FileOffset, Uint),
+        #[tag = 131]
+        SpecializedVarGet3(FileOffset, Uint),
+        #[tag = 132]
+        SpecializedVarGet4(FileOffset, Uint),
+        #[tag = 133]
+        SpecializedVarGet5(FileOffset, Uint),
+        #[tag = 134]
+        SpecializedVarGet6(FileOffset, Uint),
+        #[tag = 135]
+        SpecializedVarGet7(FileOffset, Uint),
+        #[tag = 21]
+        VarSet {
+            offset: FileOffset,
+            var_decl_position: Uint,
+            var: VarRef,
+            value: Box<Expr>,
+        },
+        #[tag = 136]
+        SpecializedVarSet0(FileOffset, Uint, Box<Expr>),
+        #[tag = 137]
+        SpecializedVarSet1(FileOffset, Uint, Box<Expr>),
+        #[tag = 138]
+        SpecializedVarSet2(FileOffset, Uint, Box<Expr>),
+        #[tag = 139]
+        SpecializedVarSet3(FileOffset, Uint, Box<Expr>),
+        #[tag = 140]
+        SpecializedVarSet4(FileOffset, Uint, Box<Expr>),
+        #[tag = 141]
+        SpecializedVarSet5(FileOffset, Uint, Box<Expr>),
+        #[tag = 142]
+        SpecializedVarSet6(FileOffset, Uint, Box<Expr>),
+        #[tag = 143]
+        SpecializedVarSet7(FileOffset, Uint, Box<Expr>),
+        /// Super property get in a mixin declaration, whose target is only known once the mixin is
+        /// applied.
+        #[tag = 22]
+        AbstractSuperPropGet {
+            offset: FileOffset,
+            name: StringRef,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 23]
+        AbstractSuperPropSet {
+            offset: FileOffset,
+            name: StringRef,
+            value: Box<Expr>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 24]
+        SuperPropGet {
+            offset: FileOffset,
+            name: StringRef,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 25]
+        SuperPropSet {
+            offset: FileOffset,
+            name: StringRef,
+            value: Box<Expr>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 118]
+        InstanceGet {
+            kind: InstanceAccessKind,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            name: StringRef,
+            typ: Box<Type>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 119]
+        InstanceSet {
+            kind: InstanceAccessKind,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            name: StringRef,
+            value: Box<Expr>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 121]
+        InstanceTearOff {
+            kind: InstanceAccessKind,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            name: StringRef,
+            typ: Box<Type>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 122]
+        DynamicGet {
+            kind: DynamicAccessKind,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            name: StringRef,
+        },
+        #[tag = 123]
+        DynamicSet {
+            kind: DynamicAccessKind,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            name: StringRef,
+            value: Box<Expr>,
+        },
+        #[tag = 26]
+        StaticGet {
+            offset: FileOffset,
+            target: CanonicalNameRef,
+        },
+        #[tag = 27]
+        StaticSet {
+            offset: FileOffset,
+            target: CanonicalNameRef,
+            value: Box<Expr>,
+        },
+        #[tag = 17]
+        StaticTearOff {
+            offset: FileOffset,
+            target: CanonicalNameRef,
+        },
+        #[tag = 60]
+        ConstructorTearOff {
+            offset: FileOffset,
+            target: CanonicalNameRef,
+        },
+        /// The target can be looked up with [`crate::component::Component::redirecting_factory`].
+        #[tag = 84]
+        RedirectingFactoryTearOff {
+            offset: FileOffset,
+            target: CanonicalNameRef,
+        },
+        #[tag = 83]
+        TypedefTearOff {
+            #[declio(with = "codecs::list")]
+            type_params: Vec<TypeParameter>,
+            expr: Box<Expr>,
+            #[declio(with = "codecs::list")]
+            type_args: Vec<Type>,
+        },
+        #[tag = 120]
+        InstanceInvoke {
+            kind: InstanceAccessKind,
+            flags: InvocationFlags,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            name: StringRef,
+            arguments: Box<Arguments>,
+            function_type: Box<Type>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 89]
+        InstanceGetterInvoke {
+            kind: InstanceAccessKind,
+            flags: InvocationFlags,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            name: StringRef,
+            arguments: Box<Arguments>,
+            function_type: Box<Type>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 124]
+        DynamicInvoke {
+            kind: DynamicAccessKind,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            name: StringRef,
+            arguments: Box<Arguments>,
+        },
+        #[tag = 125]
+        FunctionInvoke {
+            kind: FunctionAccessKind,
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            arguments: Box<Arguments>,
+            function_type: Box<Type>,
+        },
+        #[tag = 126]
+        FunctionTearOff {
+            offset: FileOffset,
+            receiver: Box<Expr>,
+        },
+        #[tag = 127]
+        LocalFunctionInvoke {
+            offset: FileOffset,
+            var_decl_position: Uint,
+            var_ref: VarRef,
+            arguments: Box<Arguments>,
+            function_type: Box<Type>,
+        },
+        #[tag = 28]
+        AbstractSuperMethodInvoke {
+            offset: FileOffset,
+            name: StringRef,
+            arguments: Box<Arguments>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 29]
+        SuperMethodInvoke {
+            offset: FileOffset,
+            name: StringRef,
+            arguments: Box<Arguments>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 30]
+        StaticInvoke {
+            offset: FileOffset,
+            target: CanonicalNameRef,
+            arguments: Box<Arguments>,
+        },
+        #[tag = 18]
+        ConstStaticInvoke {
+            offset: FileOffset,
+            target: CanonicalNameRef,
+            arguments: Box<Arguments>,
+        },
+        #[tag = 31]
+        ConstructorInvoke {
+            offset: FileOffset,
+            constructor: CanonicalNameRef,
+            arguments: Box<Arguments>,
+        },
+        #[tag = 32]
+        ConstConstructorInvoke {
+            offset: FileOffset,
+            constructor: CanonicalNameRef,
+            arguments: Box<Arguments>,
+        },
+        #[tag = 15]
+        EqualsNull { offset: FileOffset, expr: Box<Expr> },
+        #[tag = 16]
+        Equals {
+            offset: FileOffset,
+            left: Box<Expr>,
+            right: Box<Expr>,
+            function_type: Box<Type>,
+            interface_target: CanonicalNameRef,
+            interface_target_origin: CanonicalNameRef,
+        },
+        #[tag = 33]
+        Not { operand: Box<Expr> },
+        #[tag = 117]
+        NullCheck {
+            offset: FileOffset,
+            operand: Box<Expr>,
+        },
+        #[tag = 34]
+        LogicalOp {
+            left: Box<Expr>,
+            operator: LogicalOp,
+            right: Box<Expr>,
+        },
+        #[tag = 35]
+        Conditional {
+            condition: Box<Expr>,
+            then: Box<Expr>,
+            otherwise: Box<Expr>,
+            #[declio(with = "codecs::option")]
+            static_type: Option<Box<Type>>,
+        },
+        #[tag = 36]
+        StringConcat {
+            offset: FileOffset,
+            #[declio(with = "codecs::list")]
+            expressions: Vec<Expr>,
+        },
+        #[tag = 111]
+        ListConcat {
+            offset: FileOffset,
+            type_arg: Box<Type>,
+            #[declio(with = "codecs::list")]
+            expressions: Vec<Expr>,
+        },
+        #[tag = 112]
+        SetConcat {
+            offset: FileOffset,
+            type_arg: Box<Type>,
+            #[declio(with = "codecs::list")]
+            expressions: Vec<Expr>,
+        },
+        #[tag = 113]
+        MapConcat {
+            offset: FileOffset,
+            key_type: Box<Type>,
+            value_type: Box<Type>,
+            #[declio(with = "codecs::list")]
+            expressions: Vec<Expr>,
+        },
+        #[tag = 114]
+        InstanceCreate(Box<InstanceCreate>),
+        #[tag = 116]
+        FileUriLit {
+            file_uri: UriRef,
+            offset: FileOffset,
+            expression: Box<Expr>,
+        },
+        #[tag = 37]
+        IsInstanceOf {
+            offset: FileOffset,
+            flags: u8,
+            operand: Box<Expr>,
+            typ: Box<Type>,
+        },
+        #[tag = 38]
+        AsInstanceOf {
+            offset: FileOffset,
+            flags: DynamicCastFlags,
+            operand: Box<Expr>,
+            typ: Box<Type>,
+        },
+        #[tag = 39]
+        StringLit(StringRef),
+        #[tag = 144]
+        IntLitMinus3,
+        #[tag = 145]
+        IntLitMinus2,
+        #[tag = 146]
+        IntLitMinus1,
+        #[tag = 147]
+        IntLit0,
+        #[tag = 148]
+        IntLit1,
+        #[tag = 149]
+        IntLit2,
+        #[tag = 150]
+        IntLit3,
+        #[tag = 151]
+        IntLit4,
+        #[tag = 55]
+        PosIntLit(Uint),
+        #[tag = 56]
+        NegIntLit(Uint),
+        #[tag = 57]
+        BigIntLit(StringRef),
+        #[tag = 40]
+        DoubleLit(f64),
+        #[tag = 41]
+        TrueLit,
+        #[tag = 42]
+        FalseLit,
+        #[tag = 43]
+        NullLit,
+        #[tag = 44]
+        SymbolLit(StringRef),
+        #[tag = 45]
+        TypeLit(Box<Type>),
+        #[tag = 46]
+        This,
+        #[tag = 47]
+        Rethrow(FileOffset),
+        #[tag = 48]
+        Throw(FileOffset, Box<Expr>),
+        #[tag = 49]
+        ListLit {
+            offset: FileOffset,
+            type_argument: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<Expr>,
+        },
+        #[tag = 109]
+        SetLit {
+            offset: FileOffset,
+            type_argument: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<Expr>,
+        },
+        #[tag = 50]
+        MapLit {
+            offset: FileOffset,
+            key_type: Box<Type>,
+            value_type: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<LabeledExpr<Expr>>,
+        },
+        #[tag = 58]
+        ConstListLit {
+            offset: FileOffset,
+            type_argument: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<Expr>,
+        },
+        #[tag = 110]
+        ConstSetLit {
+            offset: FileOffset,
+            type_argument: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<Expr>,
+        },
+        #[tag = 59]
+        ConstMapLit {
+            offset: FileOffset,
+            key_type: Box<Type>,
+            value_type: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<LabeledExpr<Expr>>,
+        },
+        #[tag = 104]
+        RecordLit {
+            offset: FileOffset,
+            #[declio(with = "codecs::list")]
+            positional: Vec<Expr>,
+            #[declio(with = "codecs::list")]
+            named: Vec<LabeledExpr<StringRef>>,
+            record_type: Box<Type>,
+        },
+        #[tag = 105]
+        ConstRecordLit {
+            offset: FileOffset,
+            #[declio(with = "codecs::list")]
+            positional: Vec<Expr>,
+            #[declio(with = "codecs::list")]
+            named: Vec<LabeledExpr<StringRef>>,
+            record_type: Box<Type>,
+        },
+        #[tag = 101]
+        RecordIndexGet {
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            receiver_type: Box<Type>,
+            index: Uint,
+        },
+        #[tag = 102]
+        RecordNameGet {
+            offset: FileOffset,
+            receiver: Box<Expr>,
+            receiver_type: Box<Type>,
+            name: StringRef,
+        },
+        #[tag = 51]
+        Await(Box<Expr>),
+        #[tag = 52]
+        Function(FileOffset, Box<Function>),
+        #[tag = 53]
+        Let(FileOffset, Box<VarDecl>, Box<Expr>),
+        #[tag = 82]
+        Block {
+            #[declio(with = "codecs::list")]
+            body: Vec<Stmt>,
+            value: Box<Expr>,
+        },
+        #[tag = 54]
+        Instantiation {
+            expr: Box<Expr>,
+            #[declio(with = "codecs::list")]
+            type_args: Vec<Type>,
+        },
+        #[tag = 14]
+        LoadLibrary(DependencyRef),
+        #[tag = 13]
+        CheckLibraryIsLoaded(DependencyRef),
+        #[tag = 106]
+        Constant {
+            offset: FileOffset,
+            typ: Box<Type>,
+            constant: ConstantRef,
+        },
+        /// Constant inlined from another file, e.g. from a constant declared in a part.
+        #[tag = 107]
+        FileUriConstant {
+            file_uri: UriRef,
+            offset: FileOffset,
+            typ: Box<Type>,
+            constant: ConstantRef,
+        },
+        #[tag = 152]
+        Switch {
+            offset: FileOffset,
+            expr: Box<Expr>,
+            #[declio(with = "codecs::list")]
+            cases: Vec<SwitchExprCase>,
+            #[declio(with = "codecs::option")]
+            static_type: Option<Box<Type>>,
+        },
+        #[tag = 153]
+        PatternAssign {
+            offset: FileOffset,
+            pattern: Box<Pattern>,
+            value: Box<Expr>,
+        },
+    }
+}
+
+codecs::node_enum! {
+    on_encode = crate::writer::check_stmt;
+    #[derive(Debug, PartialEq)]
+    pub enum Stmt {
+        #[tag = 61]
+        Expr(Box<Expr>),
+        #[tag = 62]
+        Block {
+            range: FileRange,
+            #[declio(with = "codecs::list")]
+            statements: Vec<Stmt>,
+        },
+        #[tag = 81]
+        AssertBlock {
+            #[declio(with = "codecs::list")]
+            statements: Vec<Stmt>,
+        },
+        #[tag = 63]
+        Empty,
+        #[tag = 64]
+        Assert(Assert),
+        /// Statement that can be jumped out of with a [`Stmt::Break`]. Labels have no identity in
+        /// the binary format and are referred to by their position in a [`LabelScope`].
+        #[tag = 65]
+        Labeled { body: Box<Stmt> },
+        #[tag = 66]
+        Break { offset: FileOffset, label: LabelRef },
+        #[tag = 67]
+        While {
+            offset: FileOffset,
+            condition: Box<Expr>,
+            body: Box<Stmt>,
+        },
+        #[tag = 68]
+        Do {
+            offset: FileOffset,
+            body: Box<Stmt>,
+            condition: Box<Expr>,
+        },
+        #[tag = 69]
+        For {
+            offset: FileOffset,
+            #[declio(with = "codecs::list")]
+            vars: Vec<VarDecl>,
+            #[declio(with = "codecs::option")]
+            condition: Option<Box<Expr>>,
+            #[declio(with = "codecs::list")]
+            updates: Vec<Expr>,
+            body: Box<Stmt>,
+        },
+        #[tag = 70]
+        ForIn {
+            offset: FileOffset,
+            body_offset: FileOffset,
+            var: Box<VarDecl>,
+            iterable: Box<Expr>,
+            body: Box<Stmt>,
+        },
+        #[tag = 80]
+        AsyncForIn {
+            offset: FileOffset,
+            body_offset: FileOffset,
+            var: Box<VarDecl>,
+            iterable: Box<Expr>,
+            body: Box<Stmt>,
+        },
+        #[tag = 71]
+        Switch {
+            offset: FileOffset,
+            expr: Box<Expr>,
+            #[declio(with = "codecs::list")]
+            cases: Vec<SwitchCase>,
+        },
+        #[tag = 72]
+        ContinueSwitch {
+            offset: FileOffset,
+            case: SwitchCaseRef,
+        },
+        #[tag = 73]
+        If {
+            offset: FileOffset,
+            condition: Box<Expr>,
+            then: Box<Stmt>,
+            otherwise: Box<Stmt>,
+        },
+        #[tag = 74]
+        Return {
+            offset: FileOffset,
+            #[declio(with = "codecs::option")]
+            expr: Option<Box<Expr>>,
+        },
+        #[tag = 75]
+        TryCatch {
+            body: Box<Stmt>,
+            flags: TryCatchFlags,
+            #[declio(with = "codecs::list")]
+            catches: Vec<Catch>,
+        },
+        #[tag = 76]
+        TryFinally {
+            body: Box<Stmt>,
+            finalizer: Box<Stmt>,
+        },
+        #[tag = 77]
+        Yield {
+            offset: FileOffset,
+            flags: YieldFlags,
+            expr: Box<Expr>,
+        },
+        #[tag = 78]
+        VarDecl { var: Box<VarDecl> },
+        /// Local function declaration, bound to `var` in the enclosing scope.
+        #[tag = 79]
+        FunctionDecl {
+            offset: FileOffset,
+            var: Box<VarDecl>,
+            function: Box<Function>,
+        },
+        #[tag = 154]
+        PatternSwitch {
+            offset: FileOffset,
+            expr: Box<Expr>,
+            #[declio(with = "codecs::list")]
+            cases: Vec<PatternSwitchCase>,
+        },
+        #[tag = 155]
+        IfCase {
+            offset: FileOffset,
+            expr: Box<Expr>,
+            case: Box<PatternGuard>,
+            then: Box<Stmt>,
+            otherwise: Box<Stmt>,
+        },
+        #[tag = 156]
+        PatternVarDecl {
+            offset: FileOffset,
+            pattern: Box<Pattern>,
+            initializer: Box<Expr>,
+            flags: PatternVarDeclFlags,
+        },
+    }
+}
+
+/// Dart 3 pattern. The SDK lowers patterns before serializing a component, so the binary format
+/// has no tags for them or for the nodes that hold them. The tags used here start past the last
+/// tag of the format so that trees built with patterns can still be written and read back.
+///
+/// Only this crate reads these tags: the VM and the SDK tools reject a component holding a
+/// pattern, or any of the expressions and statements tagged from [`FIRST_CRATE_TAG`] up. Lower
+/// patterns before writing a component meant for them, components holding these tags are only
+/// written with [`EncodeOptions::crate_specific_tags`](crate::component::EncodeOptions).
+#[derive(Debug, PartialEq, Encode, Decode)]
 #[declio(id_type = "u8")]
-pub enum Expr {
-    #[declio(id = "19")]
-    Invalid {
-        offset: FileOffset,
-        message: StringRef,
-        #[declio(with = "codecs::option")]
-        expression: Option<Box<Expr>>,
-    },
-    #[declio(id = "20")]
-    VarGet {
-        offset: FileOffset,
-        var_decl_position: Uint,
-        var: VarRef,
-        #[declio(with = "codecs::option")]
-        promoted_type: Option<Box<Type>>,
-    },
-    #[declio(id = "128")]
-    SpecializedVarGet0(FileOffset, Uint),
-    #[declio(id = "129")]
-    SpecializedVarGet1(FileOffset, Uint),
-    #[declio(id = "130")]
-    SpecializedVarGet2(FileOffset, Uint),
-    #[declio(id = "131")]
-    SpecializedVarGet3(FileOffset, Uint),
-    #[declio(id = "132")]
-    SpecializedVarGet4(FileOffset, Uint),
-    #[declio(id = "133")]
-    SpecializedVarGet5(FileOffset, Uint),
-    #[declio(id = "134")]
-    SpecializedVarGet6(FileOffset, Uint),
-    #[declio(id = "135")]
-    SpecializedVarGet7(FileOffset, Uint),
-    #[declio(id = "21")]
-    VarSet {
-        offset: FileOffset,
-        var_decl_position: Uint,
-        var: VarRef,
-        value: Box<Expr>,
-    },
-    #[declio(id = "136")]
-    SpecializedVarSet0(FileOffset, Uint, Box<Expr>),
-    #[declio(id = "137")]
-    SpecializedVarSet1(FileOffset, Uint, Box<Expr>),
-    #[declio(id = "138")]
-    SpecializedVarSet2(FileOffset, Uint, Box<Expr>),
-    #[declio(id = "139")]
-    SpecializedVarSet3(FileOffset, Uint, Box<Expr>),
-    #[declio(id = "140")]
-    SpecializedVarSet4(FileOffset, Uint, Box<Expr>),
-    #[declio(id = "141")]
-    SpecializedVarSet5(FileOffset, Uint, Box<Expr>),
-    #[declio(id = "142")]
-    SpecializedVarSet6(FileOffset, Uint, Box<Expr>),
-    #[declio(id = "143")]
-    SpecializedVarSet7(FileOffset, Uint, Box<Expr>),
-    #[declio(id = "24")]
-    SuperPropGet {
-        offset: FileOffset,
-        name: StringRef,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
-    },
-    #[declio(id = "24")]
-    SuperPropSet {
+pub enum Pattern {
+    #[declio(id = "157")]
+    Constant {
         offset: FileOffset,
-        name: StringRef,
         value: Box<Expr>,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
     },
-    #[declio(id = "118")]
-    InstanceGet {
-        kind: InstanceAccessKind,
+    #[declio(id = "158")]
+    And {
         offset: FileOffset,
-        receiver: Box<Expr>,
-        name: StringRef,
-        typ: Box<Type>,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
+        left: Box<Pattern>,
+        right: Box<Pattern>,
     },
-    #[declio(id = "119")]
-    InstanceSet {
-        kind: InstanceAccessKind,
+    #[declio(id = "159")]
+    Or {
         offset: FileOffset,
-        receiver: Box<Expr>,
-        name: StringRef,
-        value: Box<Expr>,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
+        left: Box<Pattern>,
+        right: Box<Pattern>,
     },
-    #[declio(id = "121")]
-    InstanceTearOff {
-        kind: InstanceAccessKind,
+    #[declio(id = "160")]
+    Cast {
         offset: FileOffset,
-        receiver: Box<Expr>,
-        name: StringRef,
+        pattern: Box<Pattern>,
         typ: Box<Type>,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
     },
-    #[declio(id = "122")]
-    DynamicGet {
-        kind: DynamicAccessKind,
+    #[declio(id = "161")]
+    NullAssert {
         offset: FileOffset,
-        receiver: Box<Expr>,
-        name: StringRef,
-    },
-    #[declio(id = "123")]
-    DynamicSet {
-        kind: DynamicAccessKind,
-        offset: FileOffset,
-        receiver: Box<Expr>,
-        name: StringRef,
-        value: Box<Expr>,
+        pattern: Box<Pattern>,
     },
-    #[declio(id = "26")]
-    StaticGet {
-        offset: FileOffset,
-        target: CanonicalNameRef,
-    },
-    #[declio(id = "27")]
-    StaticSet {
-        offset: FileOffset,
-        target: CanonicalNameRef,
-        value: Box<Expr>,
-    },
-    #[declio(id = "17")]
-    StaticTearOff {
-        offset: FileOffset,
-        target: CanonicalNameRef,
-    },
-    #[declio(id = "60")]
-    ConstructorTearOff {
-        offset: FileOffset,
-        target: CanonicalNameRef,
-    },
-    #[declio(id = "84")]
-    RedirectingFactoryTearOff {
-        offset: FileOffset,
-        target: CanonicalNameRef,
-    },
-    #[declio(id = "83")]
-    TypedefTearOff {
-        #[declio(with = "codecs::list")]
-        type_params: Vec<TypeParameter>,
-        expr: Box<Expr>,
-        #[declio(with = "codecs::list")]
-        type_args: Vec<Type>,
-    },
-    #[declio(id = "120")]
-    InstanceInvoke {
-        kind: InstanceAccessKind,
-        flags: InvocationFlags,
-        offset: FileOffset,
-        receiver: Box<Expr>,
-        name: StringRef,
-        arguments: Box<Arguments>,
-        function_type: Box<Type>,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
-    },
-    #[declio(id = "89")]
-    InstanceGetterInvoke {
-        kind: InstanceAccessKind,
-        flags: InvocationFlags,
-        offset: FileOffset,
-        receiver: Box<Expr>,
-        name: StringRef,
-        arguments: Box<Arguments>,
-        function_type: Box<Type>,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
-    },
-    #[declio(id = "124")]
-    DynamicInvoke {
-        kind: DynamicAccessKind,
-        offset: FileOffset,
-        receiver: Box<Expr>,
-        name: StringRef,
-        arguments: Box<Arguments>,
-    },
-    #[declio(id = "125")]
-    FunctionInvoke {
-        kind: FunctionAccessKind,
-        offset: FileOffset,
-        receiver: Box<Expr>,
-        arguments: Box<Arguments>,
-        function_type: Box<Type>,
-    },
-    #[declio(id = "126")]
-    FunctionTearOff {
-        offset: FileOffset,
-        receiver: Box<Expr>,
-    },
-    #[declio(id = "127")]
-    LocalFunctionInvoke {
-        offset: FileOffset,
-        var_decl_position: Uint,
-        var_ref: VarRef,
-        arguments: Box<Arguments>,
-        function_type: Box<Type>,
-    },
-    #[declio(id = "29")]
-    SuperMethodInvoke {
-        offset: FileOffset,
-        name: StringRef,
-        arguments: Box<Arguments>,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
-    },
-    #[declio(id = "30")]
-    StaticInvoke {
-        offset: FileOffset,
-        target: CanonicalNameRef,
-        arguments: Box<Arguments>,
-    },
-    #[declio(id = "18")]
-    ConstStaticInvoke {
-        offset: FileOffset,
-        target: CanonicalNameRef,
-        arguments: Box<Arguments>,
-    },
-    #[declio(id = "31")]
-    ConstructorInvoke {
-        offset: FileOffset,
-        constructor: CanonicalNameRef,
-        arguments: Box<Arguments>,
-    },
-    #[declio(id = "32")]
-    ConstConstructorInvoke {
-        offset: FileOffset,
-        constructor: CanonicalNameRef,
-        arguments: Box<Arguments>,
-    },
-    #[declio(id = "15")]
-    EqualsNull { offset: FileOffset, expr: Box<Expr> },
-    #[declio(id = "16")]
-    Equals {
-        offset: FileOffset,
-        left: Box<Expr>,
-        right: Box<Expr>,
-        function_type: Box<Type>,
-        interface_target: CanonicalNameRef,
-        interface_target_origin: CanonicalNameRef,
-    },
-    #[declio(id = "33")]
-    Not { operand: Box<Expr> },
-    #[declio(id = "117")]
+    #[declio(id = "162")]
     NullCheck {
         offset: FileOffset,
-        operand: Box<Expr>,
-    },
-    #[declio(id = "34")]
-    LogicalOp {
-        left: Box<Expr>,
-        operator: LogicalOp,
-        right: Box<Expr>,
-    },
-    #[declio(id = "35")]
-    Conditional {
-        condition: Box<Expr>,
-        then: Box<Expr>,
-        otherwise: Box<Expr>,
-        #[declio(with = "codecs::option")]
-        static_type: Option<Box<Type>>,
-    },
-    #[declio(id = "36")]
-    StringConcat {
-        offset: FileOffset,
-        #[declio(with = "codecs::list")]
-        expressions: Vec<Expr>,
+        pattern: Box<Pattern>,
     },
-    #[declio(id = "111")]
-    ListConcat {
+    #[declio(id = "163")]
+    List {
         offset: FileOffset,
-        type_arg: Box<Type>,
+        #[declio(with = "codecs::option")]
+        type_argument: Option<Box<Type>>,
         #[declio(with = "codecs::list")]
-        expressions: Vec<Expr>,
+        patterns: Vec<Pattern>,
     },
-    #[declio(id = "112")]
-    SetConcat {
+    #[declio(id = "164")]
+    Map {
         offset: FileOffset,
-        type_arg: Box<Type>,
+        #[declio(with = "codecs::option")]
+        key_type: Option<Box<Type>>,
+        #[declio(with = "codecs::option")]
+        value_type: Option<Box<Type>>,
         #[declio(with = "codecs::list")]
-        expressions: Vec<Expr>,
+        entries: Vec<MapPatternEntry>,
     },
-    #[declio(id = "113")]
-    MapConcat {
+    /// Named fields are matched with [`Pattern::Named`].
+    #[declio(id = "165")]
+    Record {
         offset: FileOffset,
-        key_type: Box<Type>,
-        value_type: Box<Type>,
+        record_type: Box<Type>,
         #[declio(with = "codecs::list")]
-        expressions: Vec<Expr>,
-    },
-    #[declio(id = "113")]
-    InstanceCreate(Box<InstanceCreate>),
-    #[declio(id = "116")]
-    FileUriLit {
-        file_uri: UriRef,
-        offset: FileOffset,
-        expression: Box<Expr>,
-    },
-    #[declio(id = "37")]
-    IsInstanceOf {
-        offset: FileOffset,
-        flags: u8,
-        operand: Box<Expr>,
-        typ: Box<Type>,
-    },
-    #[declio(id = "38")]
-    AsInstanceOf {
-        offset: FileOffset,
-        flags: DynamicCastFlags,
-        operand: Box<Expr>,
-        typ: Box<Type>,
+        patterns: Vec<Pattern>,
     },
-    #[declio(id = "39")]
-    StringLit(StringRef),
-    #[declio(id = "144")]
-    IntLitMinus3,
-    #[declio(id = "145")]
-    IntLitMinus2,
-    #[declio(id = "146")]
-    IntLitMinus1,
-    #[declio(id = "147")]
-    IntLit0,
-    #[declio(id = "148")]
-    IntLit1,
-    #[declio(id = "149")]
-    IntLit2,
-    #[declio(id = "150")]
-    IntLit3,
-    #[declio(id = "151")]
-    IntLit4,
-    #[declio(id = "55")]
-    PosIntLit(Uint),
-    #[declio(id = "56")]
-    NegIntLit(Uint),
-    #[declio(id = "57")]
-    BigIntLit(StringRef),
-    #[declio(id = "40")]
-    DoubleLit(f64),
-    #[declio(id = "41")]
-    TrueLit,
-    #[declio(id = "42")]
-    FalseLit,
-    #[declio(id = "43")]
-    NullLit,
-    #[declio(id = "44")]
-    SymbolLit(StringRef),
-    #[declio(id = "45")]
-    TypeLit(Box<Type>),
-    #[declio(id = "46")]
-    This,
-    #[declio(id = "47")]
-    Rethrow(FileOffset),
-    #[declio(id = "48")]
-    Throw(FileOffset, Box<Expr>),
-    #[declio(id = "49")]
-    ListLit {
+    /// Fields are matched with [`Pattern::Named`].
+    #[declio(id = "166")]
+    Object {
         offset: FileOffset,
-        type_argument: Box<Type>,
+        required_type: Box<Type>,
         #[declio(with = "codecs::list")]
-        values: Vec<Expr>,
+        fields: Vec<Pattern>,
     },
-    #[declio(id = "109")]
-    SetLit {
+    #[declio(id = "167")]
+    Named {
         offset: FileOffset,
-        type_argument: Box<Type>,
-        #[declio(with = "codecs::list")]
-        values: Vec<Expr>,
+        name: StringRef,
+        pattern: Box<Pattern>,
     },
-    #[declio(id = "50")]
-    MapLit {
+    #[declio(id = "168")]
+    Relational {
         offset: FileOffset,
-        key_type: Box<Type>,
-        value_type: Box<Type>,
-        #[declio(with = "codecs::list")]
-        values: Vec<LabeledExpr<Expr>>,
-    },
-    #[declio(id = "51")]
-    Await(Box<Expr>),
-    #[declio(id = "52")]
-    Function(FileOffset, Box<Function>),
-    #[declio(id = "53")]
-    Let(FileOffset, Box<VarDecl>, Box<Expr>),
-    #[declio(id = "82")]
-    Block {
-        #[declio(with = "codecs::list")]
-        body: Vec<Stmt>,
+        kind: RelationalKind,
         value: Box<Expr>,
     },
-    #[declio(id = "54")]
-    Instantiation {
-        expr: Box<Expr>,
-        #[declio(with = "codecs::list")]
-        type_args: Vec<Type>,
-    },
-    #[declio(id = "14")]
-    LoadLibrary(DependencyRef),
-    #[declio(id = "13")]
-    CheckLibraryIsLoaded(DependencyRef),
-    #[declio(id = "106")]
-    Constant {
-        offset: FileOffset,
-        typ: Box<Type>,
-        constant: ConstantRef,
-    },
-}
-
-#[derive(Debug, Encode, Decode)]
-#[declio(id_type = "u8")]
-pub enum Stmt {
-    #[declio(id = "61")]
-    Expr(Box<Expr>),
-    #[declio(id = "62")]
-    Block {
-        range: FileRange,
-        #[declio(with = "codecs::list")]
-        statements: Vec<Stmt>,
-    },
-    #[declio(id = "81")]
-    AssertBlock {
-        #[declio(with = "codecs::list")]
-        statements: Vec<Stmt>,
-    },
-    #[declio(id = "63")]
-    Empty,
-    #[declio(id = "64")]
-    Assert(Assert),
-    #[declio(id = "65")]
-    Labeled { body: Box<Stmt> },
-    #[declio(id = "66")]
-    Break { offset: FileOffset, label: Uint },
-    #[declio(id = "67")]
-    While {
+    #[declio(id = "169")]
+    Wildcard {
         offset: FileOffset,
-        condition: Box<Expr>,
-        body: Box<Stmt>,
-    },
-    #[declio(id = "68")]
-    Do {
-        offset: FileOffset,
-        body: Box<Stmt>,
-        condition: Box<Expr>,
-    },
-    #[declio(id = "69")]
-    For {
-        offset: FileOffset,
-        #[declio(with = "codecs::list")]
-        vars: Vec<VarDecl>,
         #[declio(with = "codecs::option")]
-        condition: Option<Box<Expr>>,
-        #[declio(with = "codecs::list")]
-        updates: Vec<Expr>,
-        body: Box<Stmt>,
+        typ: Option<Box<Type>>,
     },
-    #[declio(id = "70")]
-    ForIn {
+    #[declio(id = "170")]
+    Variable {
         offset: FileOffset,
-        body_offset: FileOffset,
-        var: Box<VarDecl>,
-        iterable: Box<Expr>,
-        body: Box<Stmt>,
-    },
-    #[declio(id = "80")]
-    AsyncForIn {
-        offset: FileOffset,
-        body_offset: FileOffset,
+        #[declio(with = "codecs::option")]
+        typ: Option<Box<Type>>,
         var: Box<VarDecl>,
-        iterable: Box<Expr>,
-        body: Box<Stmt>,
-    },
-    #[declio(id = "71")]
-    Switch {
-        offset: FileOffset,
-        expr: Box<Expr>,
-        #[declio(with = "codecs::list")]
-        cases: Vec<SwitchCase>,
-    },
-    #[declio(id = "72")]
-    ContinueSwitch {
-        offset: FileOffset,
-        case_index: Uint,
     },
-    #[declio(id = "73")]
-    If {
-        offset: FileOffset,
-        condition: Box<Expr>,
-        then: Box<Stmt>,
-        otherwise: Box<Stmt>,
-    },
-    #[declio(id = "74")]
-    Return {
+    #[declio(id = "171")]
+    AssignedVariable { offset: FileOffset, var: VarRef },
+    #[declio(id = "172")]
+    Rest {
         offset: FileOffset,
         #[declio(with = "codecs::option")]
-        expr: Option<Box<Expr>>,
-    },
-    #[declio(id = "75")]
-    TryCatch {
-        body: Box<Stmt>,
-        flags: u8,
-        #[declio(with = "codecs::list")]
-        catches: Vec<Catch>,
-    },
-    #[declio(id = "76")]
-    TryFinally {
-        body: Box<Stmt>,
-        finalizer: Box<Stmt>,
-    },
-    #[declio(id = "77")]
-    Yield {
-        offset: FileOffset,
-        flags: u8,
-        expr: Box<Expr>,
-    },
-    #[declio(id = "78")]
-    VarDecl { var: Box<VarDecl> },
-    #[declio(id = "79")]
-    FunctionDecl {
-        offset: FileOffset,
-        var: Box<VarDecl>,
-        function: Box<Function>,
+        pattern: Option<Box<Pattern>>,
     },
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct MapPatternEntry {
+    pub offset: FileOffset,
+    pub key: Expr,
+    pub value: Pattern,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+#[declio(id_type = "u8")]
+pub enum RelationalKind {
+    #[declio(id = "0")]
+    Equals,
+    #[declio(id = "1")]
+    NotEquals,
+    #[declio(id = "2")]
+    LessThan,
+    #[declio(id = "3")]
+    LessThanEqual,
+    #[declio(id = "4")]
+    GreaterThan,
+    #[declio(id = "5")]
+    GreaterThanEqual,
+}
+
+/// Pattern along with the optional `when` clause guarding it.
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct PatternGuard {
+    pub pattern: Pattern,
+    #[declio(with = "codecs::option")]
+    pub guard: Option<Box<Expr>>,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct SwitchExprCase {
+    pub offset: FileOffset,
+    pub case: PatternGuard,
+    pub body: Expr,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct PatternSwitchCase {
+    pub offset: FileOffset,
+    #[declio(with = "codecs::list")]
+    pub cases: Vec<PatternGuard>,
+    pub flags: PatternSwitchCaseFlags,
+    pub body: Stmt,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct Assert {
     pub condition: Box<Expr>,
     pub file_range: FileRange,
@@ -541,7 +840,15 @@ pub struct Assert {
     pub message: Option<Box<Expr>>,
 }
 
-#[derive(Debug, Encode, Decode)]
+/// Assert of a constant constructor. It is written as an `AssertStatement` node, with the tag of
+/// [`Stmt::Assert`].
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct TaggedAssert {
+    pub tag: AssertStatementTag,
+    pub assert: Assert,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct InstanceCreate {
     pub offset: FileOffset,
     pub class: CanonicalNameRef,
@@ -550,92 +857,102 @@ pub struct InstanceCreate {
     #[declio(with = "codecs::list")]
     pub field_values: Vec<LabeledExpr<CanonicalNameRef>>,
     #[declio(with = "codecs::list")]
-    pub asserts: Vec<Assert>,
+    pub asserts: Vec<TaggedAssert>,
     #[declio(with = "codecs::list")]
     pub unused_args: Vec<Expr>,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct LabeledExpr<A: Encode + Decode> {
     pub label: A,
     pub value: Expr,
 }
 
-#[derive(Debug, Encode, Decode)]
-#[declio(id_type = "u8")]
-pub enum Constant {
-    #[declio(id = "0")]
-    Null,
-    #[declio(id = "1")]
-    Bool(u8),
-    #[declio(id = "2")]
-    Int(IntLit),
-    #[declio(id = "3")]
-    Double(f64),
-    #[declio(id = "4")]
-    String(StringRef),
-    #[declio(id = "5")]
-    Symbol(CanonicalNameRef, StringRef),
-    #[declio(id = "6")]
-    Map {
-        key_type: Box<Type>,
-        value_type: Box<Type>,
-        #[declio(with = "codecs::list")]
-        values: Vec<LabeledConstant<ConstantRef>>,
-    },
-    #[declio(id = "7")]
-    List {
-        typ: Box<Type>,
-        #[declio(with = "codecs::list")]
-        values: Vec<ConstantRef>,
-    },
-    #[declio(id = "13")]
-    Set {
-        typ: Box<Type>,
-        #[declio(with = "codecs::list")]
-        values: Vec<ConstantRef>,
-    },
-    #[declio(id = "8")]
-    Instance {
-        class: CanonicalNameRef,
-        #[declio(with = "codecs::list")]
-        type_args: Vec<Type>,
-        #[declio(with = "codecs::list")]
-        values: Vec<LabeledConstant<CanonicalNameRef>>,
-    },
-    #[declio(id = "9")]
-    Instantiation {
-        tear_off_constant: ConstantRef,
-        #[declio(with = "codecs::list")]
-        type_args: Vec<Type>,
-    },
-    #[declio(id = "10")]
-    StaticTearOff { static_procedure: CanonicalNameRef },
-    #[declio(id = "11")]
-    TypeLiteral(Type),
-    #[declio(id = "12")]
-    Unevaluated(Expr),
-    #[declio(id = "14")]
-    TypedefTearOff {
-        #[declio(with = "codecs::list")]
-        parameters: Vec<Type>,
-        static_procedure: CanonicalNameRef,
-        #[declio(with = "codecs::list")]
-        types: Vec<Type>,
-    },
-    #[declio(id = "15")]
-    ConstructorTearOff { static_procedure: CanonicalNameRef },
-    #[declio(id = "16")]
-    RedirectingFactoryTearOff { static_procedure: CanonicalNameRef },
+codecs::node_enum! {
+    #[derive(Debug, PartialEq)]
+    pub enum Constant {
+        #[tag = 0]
+        Null,
+        #[tag = 1]
+        Bool(u8),
+        #[tag = 2]
+        Int(IntLit),
+        #[tag = 3]
+        Double(f64),
+        #[tag = 4]
+        String(StringRef),
+        #[tag = 5]
+        Symbol(CanonicalNameRef, StringRef),
+        #[tag = 6]
+        Map {
+            key_type: Box<Type>,
+            value_type: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<LabeledConstant<ConstantRef>>,
+        },
+        #[tag = 7]
+        List {
+            typ: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<ConstantRef>,
+        },
+        #[tag = 13]
+        Set {
+            typ: Box<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<ConstantRef>,
+        },
+        #[tag = 8]
+        Instance {
+            class: CanonicalNameRef,
+            #[declio(with = "codecs::list")]
+            type_args: Vec<Type>,
+            #[declio(with = "codecs::list")]
+            values: Vec<LabeledConstant<CanonicalNameRef>>,
+        },
+        #[tag = 9]
+        Instantiation {
+            tear_off_constant: ConstantRef,
+            #[declio(with = "codecs::list")]
+            type_args: Vec<Type>,
+        },
+        #[tag = 10]
+        StaticTearOff { static_procedure: CanonicalNameRef },
+        #[tag = 11]
+        TypeLiteral(Type),
+        #[tag = 12]
+        Unevaluated(Expr),
+        #[tag = 14]
+        TypedefTearOff {
+            #[declio(with = "codecs::list")]
+            parameters: Vec<Type>,
+            static_procedure: CanonicalNameRef,
+            #[declio(with = "codecs::list")]
+            types: Vec<Type>,
+        },
+        #[tag = 15]
+        ConstructorTearOff { static_procedure: CanonicalNameRef },
+        /// The target can be looked up with [`crate::component::Component::redirecting_factory`].
+        #[tag = 16]
+        RedirectingFactoryTearOff { static_procedure: CanonicalNameRef },
+        #[tag = 17]
+        Record {
+            #[declio(with = "codecs::list")]
+            positional: Vec<ConstantRef>,
+            #[declio(with = "codecs::list")]
+            named: Vec<LabeledConstant<StringRef>>,
+            record_type: Box<Type>,
+        },
+    }
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct LabeledConstant<A: Encode + Decode> {
     pub key: A,
     pub value: ConstantRef,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 #[declio(id_type = "u8")]
 pub enum IntLit {
     #[declio(id = "144")]
@@ -662,7 +979,7 @@ pub enum IntLit {
     Big(StringRef),
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct Arguments {
     pub num_args: Uint,
     #[declio(with = "codecs::list")]
@@ -684,7 +1001,7 @@ impl Arguments {
     }
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct Catch {
     pub offset: FileOffset,
     pub guard: Type,
@@ -695,7 +1012,7 @@ pub struct Catch {
     pub body: Box<Stmt>,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct SwitchCase {
     #[declio(with = "codecs::list")]
     pub exprs: Vec<LabeledExpr<FileOffset>>,
@@ -703,7 +1020,67 @@ pub struct SwitchCase {
     pub body: Stmt,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+/// Target of a [`Stmt::Break`]: the index of a [`Stmt::Labeled`] among the labeled statements
+/// enclosing the break, outermost first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct LabelRef(pub Uint);
+
+/// Target of a [`Stmt::ContinueSwitch`]: the index of a [`SwitchCase`] among the cases of all
+/// switch statements enclosing the continue, outermost switch first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct SwitchCaseRef(pub Uint);
+
+/// Labeled statements and switch cases in scope while walking a member body, used to resolve
+/// [`LabelRef`] and [`SwitchCaseRef`]. Call [`LabelScope::enter`] on every statement before
+/// walking its children and [`LabelScope::exit`] once done with them.
+#[derive(Debug, Default)]
+pub struct LabelScope<'a> {
+    labels: Vec<&'a Stmt>,
+    cases: Vec<&'a SwitchCase>,
+}
+
+impl<'a> LabelScope<'a> {
+    pub fn enter(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::Labeled { .. } => self.labels.push(stmt),
+            Stmt::Switch { cases, .. } => self.cases.extend(cases),
+            _ => {}
+        }
+    }
+
+    pub fn exit(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::Labeled { .. } => {
+                self.labels.pop();
+            }
+            Stmt::Switch { cases, .. } => {
+                self.cases
+                    .truncate(self.cases.len().saturating_sub(cases.len()));
+            }
+            _ => {}
+        }
+    }
+
+    /// Label referring to the innermost labeled statement in scope.
+    pub fn innermost_label(&self) -> Option<LabelRef> {
+        let len = self.labels.len() as u32;
+        len.checked_sub(1).map(|index| LabelRef(Uint(index)))
+    }
+
+    /// Labeled statement a break to `label` jumps out of.
+    pub fn label(&self, label: LabelRef) -> Option<&'a Stmt> {
+        let LabelRef(Uint(index)) = label;
+        self.labels.get(index as usize).copied()
+    }
+
+    /// Switch case a continue to `case` jumps to.
+    pub fn case(&self, case: SwitchCaseRef) -> Option<&'a SwitchCase> {
+        let SwitchCaseRef(Uint(index)) = case;
+        self.cases.get(index as usize).copied()
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct VarDecl {
     pub offset: FileOffset,
     pub equals_sign_offset: FileOffset,
@@ -716,7 +1093,7 @@ pub struct VarDecl {
     pub initializer: Option<Box<Expr>>,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 #[declio(id_type = "u8")]
 pub enum InstanceAccessKind {
     #[declio(id = "0")]
@@ -729,7 +1106,7 @@ pub enum InstanceAccessKind {
     Nullable,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 #[declio(id_type = "u8")]
 pub enum DynamicAccessKind {
     #[declio(id = "0")]
@@ -742,7 +1119,7 @@ pub enum DynamicAccessKind {
     Unresolved,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 #[declio(id_type = "u8")]
 pub enum FunctionAccessKind {
     #[declio(id = "0")]
@@ -755,7 +1132,7 @@ pub enum FunctionAccessKind {
     Nullable,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 #[declio(id_type = "u8")]
 pub enum LogicalOp {
     #[declio(id = "0")]
@@ -763,3 +1140,265 @@ pub enum LogicalOp {
     #[declio(id = "1")]
     Or,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::builder::ComponentBuilder;
+    use crate::component::{
+        ComponentFile, ComponentHeader, EncodeOptions, Library, NonNullableMode, SourceInfo
+    };
+    use crate::node::{Nullable, Procedure};
+    use crate::testing::{sample_component, write_and_load};
+
+    #[test]
+    fn test_records_roundtrip() -> Result<(), Box<dyn Error>> {
+        let mut builder = ComponentBuilder::default();
+        builder.add_source(SourceInfo::default());
+        let lib_name = builder.add_name_from([StrRef::Borrowed("records")]);
+        let main_name = builder.add_name_from([
+            StrRef::Borrowed("records"),
+            crate::names::METHODS,
+            crate::names::MAIN,
+        ]);
+
+        let record = builder.record_literal(
+            FileOffset::new(10),
+            vec![(Expr::IntLit1, Type::Dynamic)],
+            vec![
+                (StrRef::Borrowed("y"), Expr::IntLit2, Type::Dynamic),
+                (StrRef::Borrowed("x"), Expr::TrueLit, Type::Dynamic),
+            ],
+        );
+        let Expr::RecordLit { record_type, .. } = &record else {
+            panic!("expected a record literal");
+        };
+        let receiver_type =
+            Box::new(
+                builder.record_type(Nullable::False, vec![Type::Dynamic], vec![
+                    (StrRef::Borrowed("x"), Type::Dynamic),
+                    (StrRef::Borrowed("y"), Type::Dynamic),
+                ]),
+            );
+        let Type::Record { named, .. } = &**record_type else {
+            panic!("expected a record type");
+        };
+        assert_eq!(builder.get_str(named[0].name), StrRef::Borrowed("x"));
+
+        let name = builder.add_string(StrRef::Borrowed("y"));
+        let body = Expr::RecordNameGet {
+            offset: FileOffset::NONE,
+            receiver: Box::new(record),
+            receiver_type,
+            name,
+        };
+        builder.add_library(Library {
+            canonical_name: lib_name,
+            procedures: vec![Procedure {
+                canonical_name: main_name,
+                function: Box::new(Function {
+                    body: Some(Box::new(Stmt::Return {
+                        offset: FileOffset::NONE,
+                        expr: Some(Box::new(body)),
+                    })),
+                    ..Function::default()
+                }),
+                ..Procedure::default()
+            }
+            .into()],
+            ..Library::default()
+        });
+
+        let comp = builder.into_component(main_name, NonNullableMode::Strong);
+        let (bytes, loaded) = write_and_load(&comp)?;
+        let comp = loaded.into_component()?;
+
+        let main = comp.libraries[0].procedures[0].decoded().unwrap();
+        let Some(Stmt::Return {
+            expr: Some(expr), ..
+        }) = main.function.body.as_deref()
+        else {
+            panic!("expected a return statement");
+        };
+        let Expr::RecordNameGet { receiver, .. } = &**expr else {
+            panic!("expected a record field access");
+        };
+        let Expr::RecordLit { offset, named, .. } = &**receiver else {
+            panic!("expected a record literal");
+        };
+        assert_eq!(offset.get(), Some(10));
+        assert_eq!(named.len(), 2);
+        assert!(matches!(named[0].value, Expr::TrueLit));
+
+        let (rewritten, _) = write_and_load(&comp)?;
+        assert_eq!(rewritten, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_patterns_roundtrip() -> Result<(), Box<dyn Error>> {
+        let var = VarDecl::default();
+        let case = PatternGuard {
+            pattern: Pattern::Object {
+                offset: FileOffset::new(4),
+                required_type: Box::new(Type::Dynamic),
+                fields: vec![Pattern::Named {
+                    offset: FileOffset::NONE,
+                    name: StringRef::UNDEFINED,
+                    pattern: Box::new(Pattern::Relational {
+                        offset: FileOffset::NONE,
+                        kind: RelationalKind::GreaterThan,
+                        value: Box::new(Expr::IntLit0),
+                    }),
+                }],
+            },
+            guard: Some(Box::new(Expr::TrueLit)),
+        };
+        let switch = Expr::Switch {
+            offset: FileOffset::NONE,
+            expr: Box::new(Expr::NullLit),
+            cases: vec![SwitchExprCase {
+                offset: FileOffset::NONE,
+                case: PatternGuard {
+                    pattern: Pattern::Variable {
+                        offset: FileOffset::NONE,
+                        typ: None,
+                        var: Box::new(var),
+                    },
+                    guard: None,
+                },
+                body: Expr::IntLit1,
+            }],
+            static_type: None,
+        };
+        let stmt = Stmt::IfCase {
+            offset: FileOffset::new(2),
+            expr: Box::new(switch),
+            case: Box::new(case),
+            then: Box::new(Stmt::Empty),
+            otherwise: Box::new(Stmt::Empty),
+        };
+
+        let mut bytes = vec![];
+        stmt.encode((), ENDIANESS, &mut bytes)?;
+        let decoded = Stmt::decode((), ENDIANESS, &mut bytes.as_slice())?;
+        let Stmt::IfCase { expr, case, .. } = &decoded else {
+            panic!("expected an if-case statement");
+        };
+        assert!(matches!(**expr, Expr::Switch { .. }));
+        assert!(matches!(case.pattern, Pattern::Object { .. }));
+
+        let mut rewritten = vec![];
+        decoded.encode((), ENDIANESS, &mut rewritten)?;
+        assert_eq!(rewritten, bytes);
+
+        let stmt = Stmt::PatternSwitch {
+            offset: FileOffset::NONE,
+            expr: Box::new(Expr::NullLit),
+            cases: vec![PatternSwitchCase {
+                offset: FileOffset::NONE,
+                cases: vec![],
+                flags: PatternSwitchCaseFlags::new().with_is_default(true),
+                body: Stmt::PatternVarDecl {
+                    offset: FileOffset::NONE,
+                    pattern: Box::new(Pattern::Wildcard {
+                        offset: FileOffset::NONE,
+                        typ: None,
+                    }),
+                    initializer: Box::new(Expr::NullLit),
+                    flags: PatternVarDeclFlags::new().with_is_final(true),
+                },
+            }],
+        };
+        let mut bytes = vec![];
+        stmt.encode((), ENDIANESS, &mut bytes)?;
+        assert_eq!(Stmt::decode((), ENDIANESS, &mut bytes.as_slice())?, stmt);
+
+        let (mut comp, _) = sample_component();
+        let main = comp.libraries[0].procedures[0].decoded_mut().unwrap();
+        main.function.body = Some(Box::new(stmt));
+        assert!(write_and_load(&comp).is_err());
+
+        let opts = EncodeOptions {
+            crate_specific_tags: true,
+        };
+        let mut bytes = vec![];
+        comp.encode_with(&ComponentHeader::DART_2_16_0_134, &opts, &mut bytes)?;
+        let loaded = ComponentFile::from_bytes(bytes)?.into_component()?;
+        assert_eq!(loaded.libraries[0].procedures, comp.libraries[0].procedures);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_label_resolution() -> Result<(), Box<dyn Error>> {
+        fn walk<'a>(stmt: &'a Stmt, scope: &mut LabelScope<'a>, jumps: &mut Vec<&'a Stmt>) {
+            scope.enter(stmt);
+            match stmt {
+                Stmt::Labeled { body } => walk(body, scope, jumps),
+                Stmt::Block { statements, .. } => {
+                    statements.iter().for_each(|stmt| walk(stmt, scope, jumps))
+                }
+                Stmt::Switch { cases, .. } => {
+                    cases.iter().for_each(|case| walk(&case.body, scope, jumps))
+                }
+                Stmt::TryCatch { body, .. } => walk(body, scope, jumps),
+                Stmt::Break { label, .. } => jumps.push(scope.label(*label).unwrap()),
+                Stmt::ContinueSwitch { case, .. } => jumps.push(&scope.case(*case).unwrap().body),
+                _ => {}
+            }
+            scope.exit(stmt);
+        }
+
+        let case = |body| SwitchCase {
+            exprs: vec![],
+            is_default: 0,
+            body,
+        };
+        let stmt = Stmt::Labeled {
+            body: Box::new(Stmt::Block {
+                range: FileRange::default(),
+                statements: vec![
+                    Stmt::Switch {
+                        offset: FileOffset::NONE,
+                        expr: Box::new(Expr::NullLit),
+                        cases: vec![
+                            case(Stmt::Empty),
+                            case(Stmt::ContinueSwitch {
+                                offset: FileOffset::NONE,
+                                case: SwitchCaseRef(Uint(0)),
+                            }),
+                        ],
+                    },
+                    Stmt::TryCatch {
+                        body: Box::new(Stmt::Break {
+                            offset: FileOffset::NONE,
+                            label: LabelRef(Uint(0)),
+                        }),
+                        flags: TryCatchFlags::new().with_needs_stack_trace(true),
+                        catches: vec![],
+                    },
+                ],
+            }),
+        };
+
+        let mut bytes = vec![];
+        stmt.encode((), ENDIANESS, &mut bytes)?;
+        let decoded = Stmt::decode((), ENDIANESS, &mut bytes.as_slice())?;
+
+        let mut scope = LabelScope::default();
+        assert!(scope.innermost_label().is_none());
+        let mut jumps = vec![];
+        walk(&decoded, &mut scope, &mut jumps);
+        assert!(matches!(jumps[..], [Stmt::Empty, Stmt::Labeled { .. }]));
+        assert!(std::ptr::eq(jumps[1], &decoded));
+
+        scope.enter(&decoded);
+        assert_eq!(scope.innermost_label(), Some(LabelRef(Uint(0))));
+
+        Ok(())
+    }
+}
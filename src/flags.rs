@@ -4,8 +4,10 @@ use declio::ctx::Endian;
 use declio::{Decode, Encode};
 use modular_bitfield::prelude::*;
 
+use crate::prim::{Uint, Version};
+
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct LibraryFlags {
     pub is_synthetic: bool,
     pub is_non_nullable_by_default: bool,
@@ -15,7 +17,7 @@ pub struct LibraryFlags {
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct DependencyFlags {
     pub is_export: bool,
     pub is_deferred: bool,
@@ -24,7 +26,7 @@ pub struct DependencyFlags {
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct CombinatorFlags {
     pub is_show: bool,
     #[skip]
@@ -32,7 +34,7 @@ pub struct CombinatorFlags {
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ClassFlags {
     pub is_abstract: bool,
     pub is_enum: bool,
@@ -41,12 +43,42 @@ pub struct ClassFlags {
     pub is_mixin_declaration: bool,
     pub has_const_constructor: bool,
     pub is_macro: bool,
+    pub is_sealed: bool,
+    pub is_mixin_class: bool,
+    pub is_base: bool,
+    pub is_interface: bool,
+    pub is_final: bool,
     #[skip]
-    remainder: B1,
+    remainder: B4,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtensionFlags {
+    pub is_extension_type_declaration: bool,
+    pub is_unnamed_extension: bool,
+    #[skip]
+    remainder: B6,
+}
+
+/// Flags of an extension type declaration. The SDK writes them but defines none of the bits yet,
+/// so they are kept as they are.
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtensionTypeDeclarationFlags {
+    pub reserved: B8,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemberDescriptorFlags {
+    pub is_static: bool,
+    #[skip]
+    remainder: B7,
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct FieldFlags {
     pub is_internal_impl: bool,
     #[skip]
@@ -63,7 +95,7 @@ pub struct FieldFlags {
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ConstructorFlags {
     pub is_const: bool,
     pub is_external: bool,
@@ -74,7 +106,7 @@ pub struct ConstructorFlags {
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ProcedureFlags {
     pub is_static: bool,
     pub is_abstract: bool,
@@ -88,7 +120,17 @@ pub struct ProcedureFlags {
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RedirectingFactoryFlags {
+    pub is_const: bool,
+    pub is_external: bool,
+    pub is_non_nullable_by_default: bool,
+    #[skip]
+    remainder: B5,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct InvocationFlags {
     pub is_invariant: bool,
     pub is_bounds_safe: bool,
@@ -97,7 +139,7 @@ pub struct InvocationFlags {
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct DynamicCastFlags {
     pub is_type_error: bool,
     pub is_covariance_check: bool,
@@ -108,7 +150,7 @@ pub struct DynamicCastFlags {
 }
 
 #[bitfield]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct VarDeclFlags {
     pub is_final: bool,
     pub is_const: bool,
@@ -120,6 +162,79 @@ pub struct VarDeclFlags {
     pub is_lowered: bool,
 }
 
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YieldFlags {
+    pub is_yield_star: bool,
+    #[skip]
+    remainder: B7,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TryCatchFlags {
+    pub needs_stack_trace: bool,
+    pub is_synthetic: bool,
+    #[skip]
+    remainder: B6,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatternVarDeclFlags {
+    pub is_final: bool,
+    #[skip]
+    remainder: B7,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatternSwitchCaseFlags {
+    pub is_default: bool,
+    #[skip]
+    remainder: B7,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InferredTypeFlags {
+    pub is_nullable: bool,
+    pub is_int: bool,
+    pub skip_check: bool,
+    pub is_constant: bool,
+    pub receiver_not_int: bool,
+    #[skip]
+    remainder: B3,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcedureAttributeFlags {
+    pub has_dynamic_uses: bool,
+    pub has_non_this_uses: bool,
+    pub has_tear_off_uses: bool,
+    #[skip]
+    remainder: B5,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectCallFlags {
+    pub check_receiver_for_null: bool,
+    pub is_closure: bool,
+    #[skip]
+    remainder: B6,
+}
+
+#[bitfield]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableSelectorFlags {
+    pub called_on_null: bool,
+    pub torn_off: bool,
+    #[skip]
+    remainder: B6,
+}
+
 macro_rules! impl_flags_codec {
     ($ty:ty) => {
         impl Encode for $ty {
@@ -155,10 +270,64 @@ macro_rules! impl_flags_codec {
 impl_flags_codec!(LibraryFlags);
 impl_flags_codec!(DependencyFlags);
 impl_flags_codec!(CombinatorFlags);
-impl_flags_codec!(ClassFlags);
+impl_flags_codec!(ExtensionFlags);
+impl_flags_codec!(ExtensionTypeDeclarationFlags);
+impl_flags_codec!(MemberDescriptorFlags);
 impl_flags_codec!(FieldFlags);
 impl_flags_codec!(ConstructorFlags);
 impl_flags_codec!(ProcedureFlags);
+impl_flags_codec!(RedirectingFactoryFlags);
 impl_flags_codec!(InvocationFlags);
 impl_flags_codec!(DynamicCastFlags);
 impl_flags_codec!(VarDeclFlags);
+impl_flags_codec!(YieldFlags);
+impl_flags_codec!(TryCatchFlags);
+impl_flags_codec!(PatternVarDeclFlags);
+impl_flags_codec!(PatternSwitchCaseFlags);
+impl_flags_codec!(InferredTypeFlags);
+impl_flags_codec!(ProcedureAttributeFlags);
+impl_flags_codec!(DirectCallFlags);
+impl_flags_codec!(TableSelectorFlags);
+
+impl Encode<Version> for ClassFlags {
+    fn encode<W>(&self, version: Version, endian: Endian, out: &mut W) -> Result<(), declio::Error>
+    where
+        W: io::Write,
+    {
+        let bits = u16::from_le_bytes(self.into_bytes());
+        if version >= Version::CLASS_MODIFIERS {
+            Uint(bits.into()).encode((), endian, out)
+        } else {
+            let bits = u8::try_from(bits).map_err(|_| {
+                declio::Error::new(format_args!(
+                    "Class modifiers need format version {} or later",
+                    Version::CLASS_MODIFIERS.0
+                ))
+            })?;
+            bits.encode((), endian, out)
+        }
+    }
+}
+
+impl Decode<Version> for ClassFlags {
+    fn decode<R>(version: Version, endian: Endian, inp: &mut R) -> Result<Self, declio::Error>
+    where
+        R: io::Read,
+    {
+        let bits = if version >= Version::CLASS_MODIFIERS {
+            let Uint(bits) = Uint::decode((), endian, inp)?;
+            u16::try_from(bits)
+                .map_err(|_| declio::Error::new(format_args!("Invalid class flags {bits}")))?
+        } else {
+            u8::decode((), endian, inp)?.into()
+        };
+        Ok(Self::from_bytes(bits.to_le_bytes()))
+    }
+}
+
+impl Default for ClassFlags {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,11 +1,17 @@
 pub mod builder;
 mod codecs;
 pub mod component;
+mod error;
 pub mod expr;
 #[allow(dead_code, clippy::return_self_not_must_use)]
 pub mod flags;
+pub mod metadata;
 pub mod names;
 pub mod node;
 pub mod prim;
+pub mod tags;
+#[cfg(test)]
+mod testing;
 mod writer;
 pub use declio;
+pub use error::{Error, PathSegment};
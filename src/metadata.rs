@@ -0,0 +1,425 @@
+use std::collections::{BTreeMap, HashMap};
+
+use declio::{Decode, Encode};
+
+use crate::codecs;
+use crate::expr::Expr;
+use crate::flags::{
+    DirectCallFlags, InferredTypeFlags, ProcedureAttributeFlags, TableSelectorFlags
+};
+use crate::prim::{CanonicalNameRef, ConstantRef, StringRef, Uint, ENDIANESS};
+
+pub const INFERRED_TYPE: &str = "vm.inferred-type.metadata";
+pub const PROCEDURE_ATTRIBUTES: &str = "vm.procedure-attributes.metadata";
+pub const DIRECT_CALL: &str = "vm.direct-call.metadata";
+pub const UNBOXING_INFO: &str = "vm.unboxing-info.metadata";
+pub const TABLE_SELECTOR: &str = "vm.table-selector.metadata";
+pub const UNREACHABLE: &str = "vm.unreachable.metadata";
+
+/// A metadata repository: payloads of a single kind attached to nodes of the component.
+#[derive(Debug, Default, Clone)]
+pub struct MetadataRepository {
+    pub tag: StringRef,
+    pub entries: Vec<MetadataEntry>,
+}
+
+impl MetadataRepository {
+    pub fn new(tag: StringRef) -> Self {
+        Self {
+            tag,
+            entries: vec![],
+        }
+    }
+
+    pub fn attach(&mut self, node: MetadataTarget, payload: Vec<u8>) {
+        self.entries.push(MetadataEntry {
+            node,
+            payload,
+            payload_offset: None,
+        });
+    }
+
+    pub fn get(&self, node: MetadataTarget) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|entry| entry.node == node)
+            .map(|entry| entry.payload.as_slice())
+    }
+
+    pub fn attach_typed<M: Metadata>(
+        &mut self,
+        node: MetadataTarget,
+        metadata: &M,
+    ) -> Result<(), declio::Error> {
+        let mut payload = vec![];
+        metadata.encode((), ENDIANESS, &mut payload)?;
+        self.attach(node, payload);
+        Ok(())
+    }
+
+    /// Decodes every payload of a decoded repository, keyed by the offset of the node it
+    /// annotates.
+    pub fn decode_typed<M: Metadata>(&self) -> Result<BTreeMap<u32, M>, declio::Error> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let offset = match entry.node {
+                    MetadataTarget::Component => 0,
+                    MetadataTarget::Offset(offset) => offset,
+                    MetadataTarget::Node(name) => {
+                        return Err(declio::Error::new(format_args!(
+                            "Metadata attached to an unresolved node {name:?}"
+                        )))
+                    }
+                };
+                Ok((offset, declio::from_bytes(&entry.payload, ENDIANESS)?))
+            })
+            .collect()
+    }
+}
+
+/// Payload of one of the well-known metadata repositories.
+pub trait Metadata: Encode + Decode {
+    const TAG: &'static str;
+}
+
+/// Result of type flow analysis for an expression, variable or member.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct InferredType {
+    pub concrete_class: CanonicalNameRef,
+    pub flags: InferredTypeFlags,
+    #[declio(skip_if = "!flags.is_constant()")]
+    pub constant: ConstantRef,
+}
+
+impl Metadata for InferredType {
+    const TAG: &'static str = INFERRED_TYPE;
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct ProcedureAttributes {
+    pub flags: ProcedureAttributeFlags,
+    pub method_or_setter_selector_id: Uint,
+    pub getter_selector_id: Uint,
+}
+
+impl Metadata for ProcedureAttributes {
+    const TAG: &'static str = PROCEDURE_ATTRIBUTES;
+}
+
+/// Devirtualized target of a call site.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct DirectCall {
+    pub target: CanonicalNameRef,
+    pub flags: DirectCallFlags,
+}
+
+impl Metadata for DirectCall {
+    const TAG: &'static str = DIRECT_CALL;
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct UnboxingInfo {
+    #[declio(with = "codecs::list")]
+    pub args: Vec<UnboxingKind>,
+    pub returned: UnboxingKind,
+}
+
+impl Metadata for UnboxingInfo {
+    const TAG: &'static str = UNBOXING_INFO;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[declio(id_type = "u8")]
+pub enum UnboxingKind {
+    #[declio(id = "0")]
+    Boxed,
+    #[declio(id = "1")]
+    Int,
+    #[declio(id = "2")]
+    Double,
+    #[declio(id = "3")]
+    Record,
+}
+
+/// Dispatch table selectors, attached to the component.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct TableSelectors {
+    #[declio(with = "codecs::list")]
+    pub selectors: Vec<TableSelector>,
+}
+
+impl Metadata for TableSelectors {
+    const TAG: &'static str = TABLE_SELECTOR;
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct TableSelector {
+    pub call_count: Uint,
+    pub flags: TableSelectorFlags,
+}
+
+/// Marks a node as unreachable, the payload is empty.
+#[derive(Debug, Clone, Copy, Encode, Decode)]
+pub struct Unreachable;
+
+impl Metadata for Unreachable {
+    const TAG: &'static str = UNREACHABLE;
+}
+
+#[derive(Debug, Clone)]
+pub struct MetadataEntry {
+    pub node: MetadataTarget,
+    pub payload: Vec<u8>,
+    /// Offset of the payload among the payloads of the component it was read from. The SDK
+    /// interleaves the payloads of all repositories, so they are written back in the order of
+    /// these offsets, followed by the payloads attached since.
+    pub payload_offset: Option<u32>,
+}
+
+/// Node a metadata payload is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MetadataTarget {
+    /// The component itself.
+    Component,
+    /// A library, class or member, resolved to its offset when the component is written.
+    Node(CanonicalNameRef),
+    /// A node offset relative to the start of the component, as found in decoded components or
+    /// in the [`ExprOffsets`] returned by [`crate::component::Component::encode`].
+    Offset(u32),
+}
+
+/// Offsets at which [`crate::component::Component::encode`] wrote the expressions of a component,
+/// so that expression-level metadata like [`DirectCall`] can target them with
+/// [`MetadataTarget::Offset`].
+#[derive(Debug, Default)]
+pub struct ExprOffsets {
+    /// Address of every expression along with its offset, sorted by address.
+    offsets: Vec<(usize, u32)>,
+}
+
+impl ExprOffsets {
+    pub(crate) fn new(mut offsets: Vec<(usize, u32)>) -> Self {
+        offsets.sort_unstable_by_key(|(addr, _)| *addr);
+        Self { offsets }
+    }
+
+    /// Offset of an expression of the component that was written. Expressions are looked up by
+    /// address, so the component must not change in between, except for its metadata: it is
+    /// written after the libraries, so attaching it does not move any expression.
+    pub fn get(&self, expr: &Expr) -> Option<u32> {
+        let addr = expr as *const Expr as usize;
+        let i = self
+            .offsets
+            .binary_search_by_key(&addr, |(addr, _)| *addr)
+            .ok()?;
+        Some(self.offsets[i].1)
+    }
+}
+
+/// Offsets of the named nodes of a component, recorded by the writer.
+#[derive(Debug, Default)]
+pub(crate) struct NodeOffsets {
+    named: HashMap<CanonicalNameRef, u32>,
+}
+
+impl NodeOffsets {
+    pub fn record<I>(&mut self, nodes: I)
+    where
+        I: IntoIterator<Item = (CanonicalNameRef, u32)>,
+    {
+        self.named.extend(nodes);
+    }
+
+    pub fn resolve(&self, target: MetadataTarget) -> Result<u32, declio::Error> {
+        match target {
+            MetadataTarget::Component => Ok(0),
+            MetadataTarget::Node(name) => self.named.get(&name).copied().ok_or_else(|| {
+                declio::Error::new(format_args!(
+                    "Metadata attached to an unknown node {name:?}"
+                ))
+            }),
+            MetadataTarget::Offset(offset) => Ok(offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io;
+
+    use super::*;
+    use crate::component::{ComponentFile, ComponentHeader, NonNullableMode};
+    use crate::expr::Stmt;
+    use crate::prim::StrRef;
+    use crate::testing::{sample_builder, write_and_load};
+
+    #[test]
+    fn test_metadata_roundtrip() -> Result<(), Box<dyn Error>> {
+        let (mut builder, main_name) = sample_builder();
+        let tag = StrRef::Borrowed(UNREACHABLE);
+        builder.add_metadata(tag.clone(), MetadataTarget::Node(main_name), vec![]);
+        let tag = StrRef::Borrowed(TABLE_SELECTOR);
+        builder.add_metadata(tag, MetadataTarget::Component, vec![1, 2, 3]);
+
+        let comp = builder.into_component(main_name, NonNullableMode::Strong);
+        let (_, mut loaded) = write_and_load(&comp)?;
+        let unreachable = loaded.metadata_repository(UNREACHABLE)?.unwrap();
+        assert_eq!(unreachable.entries.len(), 1);
+        assert!(unreachable.entries[0].payload.is_empty());
+
+        let selectors = loaded
+            .metadata_repository(TABLE_SELECTOR)?
+            .unwrap();
+        assert_eq!(
+            selectors.get(MetadataTarget::Offset(0)),
+            Some([1, 2, 3].as_slice())
+        );
+
+        assert!(loaded
+            .metadata_repository(INFERRED_TYPE)?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata_payload_order() -> Result<(), Box<dyn Error>> {
+        let (mut builder, main_name) = sample_builder();
+        let main = MetadataTarget::Node(main_name);
+        builder.add_metadata(StrRef::Borrowed(UNREACHABLE), main, vec![1]);
+        builder.add_metadata(StrRef::Borrowed(INFERRED_TYPE), main, vec![2, 3]);
+        let mut comp = builder.into_component(main_name, NonNullableMode::Strong);
+        // payloads of the second repository come first, as when the SDK interleaves them
+        for (repo, offset) in comp.metadata.iter_mut().zip([2, 0]) {
+            repo.entries[0].payload_offset = Some(offset);
+        }
+        let (bytes, mut loaded) = write_and_load(&comp)?;
+        let mappings = loaded.metadata_mappings()?;
+        assert_eq!(mappings[0].entries[0].1, 2);
+        assert_eq!(mappings[1].entries[0].1, 0);
+
+        let comp = loaded.into_component()?;
+        assert_eq!(comp.metadata[0].entries[0].payload_offset, Some(2));
+        let (rewritten, _) = write_and_load(&comp)?;
+        assert_eq!(rewritten, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_typed_metadata_roundtrip() -> Result<(), Box<dyn Error>> {
+        let (mut builder, main_name) = sample_builder();
+        let main = MetadataTarget::Node(main_name);
+        let inferred = InferredType {
+            concrete_class: main_name,
+            flags: InferredTypeFlags::new()
+                .with_is_nullable(true)
+                .with_is_constant(true),
+            constant: ConstantRef::new(Uint(1)),
+        };
+        builder.add_typed_metadata(main, &inferred)?;
+        let attributes = ProcedureAttributes {
+            flags: ProcedureAttributeFlags::new().with_has_tear_off_uses(true),
+            method_or_setter_selector_id: Uint(3),
+            getter_selector_id: Uint(4),
+        };
+        builder.add_typed_metadata(main, &attributes)?;
+        let unboxing = UnboxingInfo {
+            args: vec![UnboxingKind::Int, UnboxingKind::Double],
+            returned: UnboxingKind::Record,
+        };
+        builder.add_typed_metadata(main, &unboxing)?;
+        builder.add_typed_metadata(main, &Unreachable)?;
+        let selectors = TableSelectors {
+            selectors: vec![TableSelector {
+                call_count: Uint(2),
+                flags: TableSelectorFlags::new().with_called_on_null(true),
+            }],
+        };
+        builder.add_typed_metadata(MetadataTarget::Component, &selectors)?;
+
+        let comp = builder.into_component(main_name, NonNullableMode::Strong);
+        let (_, mut loaded) = write_and_load(&comp)?;
+        let main_offset = loaded.library_entries()?[0].procedures[0].range.start;
+
+        let inferred = loaded
+            .typed_<InferredType>()?
+            .remove(&main_offset)
+            .unwrap();
+        assert_eq!(inferred.concrete_class, main_name);
+        assert!(inferred.flags.is_nullable() && inferred.flags.is_constant());
+        assert_eq!(u32::from(inferred.constant), 1);
+
+        let attributes = loaded
+            .typed_<ProcedureAttributes>()?
+            .remove(&main_offset)
+            .unwrap();
+        assert!(attributes.flags.has_tear_off_uses() && !attributes.flags.has_dynamic_uses());
+        assert_eq!(attributes.method_or_setter_selector_id, Uint(3));
+        assert_eq!(attributes.getter_selector_id, Uint(4));
+
+        let unboxing = loaded
+            .typed_<UnboxingInfo>()?
+            .remove(&main_offset)
+            .unwrap();
+        assert_eq!(unboxing.args, [UnboxingKind::Int, UnboxingKind::Double]);
+        assert_eq!(unboxing.returned, UnboxingKind::Record);
+
+        let unreachable = loaded.typed_<Unreachable>()?;
+        assert_eq!(unreachable.keys().copied().collect::<Vec<_>>(), [
+            main_offset
+        ]);
+
+        let selectors = loaded
+            .typed_<TableSelectors>()?
+            .remove(&0)
+            .unwrap();
+        assert_eq!(selectors.selectors.len(), 1);
+        assert_eq!(selectors.selectors[0].call_count, Uint(2));
+        assert!(selectors.selectors[0].flags.called_on_null());
+
+        assert!(loaded.typed_<DirectCall>()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expr_offsets() -> Result<(), Box<dyn Error>> {
+        let (mut builder, main_name) = sample_builder();
+        let tag = builder.add_string(StrRef::Borrowed(DIRECT_CALL));
+        let mut comp = builder.into_component(main_name, NonNullableMode::Strong);
+        let header = ComponentHeader::DART_2_16_0_134;
+
+        let offsets = comp.encode(&header, &mut io::sink())?;
+        let main = comp.libraries[0].procedures[0].decoded().unwrap();
+        let offset = match main.function.body.as_deref() {
+            Some(Stmt::Expr(expr)) => offsets.get(expr).unwrap(),
+            _ => panic!("main should consist of a single call"),
+        };
+        let mut repo = MetadataRepository::new(tag);
+        let call = DirectCall {
+            target: main_name,
+            flags: DirectCallFlags::new(),
+        };
+        repo.attach_typed(MetadataTarget::Offset(offset), &call)?;
+        comp.metadata.push(repo);
+
+        let mut bytes = vec![];
+        let reencoded = comp.encode(&header, &mut bytes)?;
+        let main = comp.libraries[0].procedures[0].decoded().unwrap();
+        if let Some(Stmt::Expr(expr)) = main.function.body.as_deref() {
+            assert_eq!(reencoded.get(expr), Some(offset));
+        }
+        let expr = Expr::decode((), ENDIANESS, &mut &bytes[offset as usize..])?;
+        assert!(matches!(expr, Expr::StaticInvoke { .. }));
+
+        let mut loaded = ComponentFile::from_bytes(bytes.as_slice())?;
+        let calls = loaded.typed_<DirectCall>()?;
+        assert_eq!(calls.keys().copied().collect::<Vec<_>>(), [offset]);
+        assert_eq!(calls[&offset].target, main_name);
+
+        Ok(())
+    }
+}
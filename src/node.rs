@@ -2,11 +2,16 @@ use declio::ctx::Endian;
 use declio::{Decode, Encode};
 
 use crate::codecs;
+use crate::codecs::members::Member;
+use crate::component::MaybeDecoded;
+use crate::error::PathSegment;
 use crate::expr::{Arguments, Assert, Expr, Stmt, VarDecl};
-use crate::flags::{ClassFlags, CombinatorFlags, ConstructorFlags, FieldFlags, ProcedureFlags};
+use crate::flags::{
+    ClassFlags, CombinatorFlags, ConstructorFlags, ExtensionFlags, ExtensionTypeDeclarationFlags, FieldFlags, MemberDescriptorFlags, ProcedureFlags, RedirectingFactoryFlags
+};
 use crate::prim::*;
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct Procedure {
     pub tag: ProcedureTag,
     pub canonical_name: CanonicalNameRef,
@@ -25,13 +30,15 @@ pub struct Procedure {
     pub function: Box<Function>,
 }
 
-#[derive(Debug, Default, Decode)]
+#[derive(Debug, Default, PartialEq, Decode)]
+#[declio(ctx = "version: Version")]
 pub struct Class {
     pub tag: ClassTag,
     pub canonical_name: CanonicalNameRef,
     pub file_uri: UriRef,
     pub start_offset: FileOffset,
     pub definition_range: FileRange,
+    #[declio(ctx = "version")]
     pub flags: ClassFlags,
     pub name: StringRef,
     #[declio(with = "codecs::list")]
@@ -44,19 +51,20 @@ pub struct Class {
     pub mixed_in_type: Option<Type>,
     #[declio(with = "codecs::list")]
     pub implemented_classes: Vec<Type>,
-    #[declio(with = "codecs::list")]
+    #[declio(with = "codecs::members")]
     pub fields: Vec<Field>,
-    #[declio(with = "codecs::list")]
+    #[declio(with = "codecs::members")]
     pub constructors: Vec<Constructor>,
     #[declio(with = "codecs::list")]
-    pub procedures: Vec<Procedure>,
-    #[declio(with = "codecs::list")]
+    pub procedures: Vec<MaybeDecoded<Procedure>>,
+    #[declio(with = "codecs::members")]
     pub redirecting_factories: Vec<RedirectingFactory>,
     #[declio(ctx = "procedures.len()")]
     pub index: ClassIndex,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
+#[declio(ctx = "version: Version")]
 pub struct Extension {
     pub tag: ExtensionTag,
     pub canonical_name: CanonicalNameRef,
@@ -65,15 +73,98 @@ pub struct Extension {
     pub annotations: Vec<Expr>,
     pub file_uri: UriRef,
     pub offset: FileOffset,
-    pub flags: u8,
+    pub flags: ExtensionFlags,
     #[declio(with = "codecs::list")]
     pub type_params: Vec<TypeParameter>,
     pub on_type: Type,
-    #[declio(with = "codecs::option")]
-    pub show_hide_clause: Option<ExtensionShowClause>,
+    #[declio(with = "codecs::option", skip_if = "version >= Version::NO_SHOW_HIDE")]
+    pub show_hide_clause: Option<ExtensionShowHideClause>,
+    #[declio(with = "codecs::list", ctx = "version")]
+    pub members: Vec<ExtensionMemberDescriptor>,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+#[declio(ctx = "version: Version")]
+pub struct ExtensionMemberDescriptor {
+    pub name: StringRef,
+    pub kind: ExtensionMemberKind,
+    pub flags: MemberDescriptorFlags,
+    pub member: CanonicalNameRef,
+    #[declio(skip_if = "version < Version::EXTENSION_TYPES")]
+    pub tear_off: CanonicalNameRef,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[declio(id_type = "u8")]
+pub enum ExtensionMemberKind {
+    #[declio(id = "0")]
+    Field,
+    #[declio(id = "1")]
+    Method,
+    #[declio(id = "2")]
+    Getter,
+    #[declio(id = "3")]
+    Setter,
+    #[declio(id = "4")]
+    Operator,
+    #[declio(id = "5")]
+    TearOff,
+}
+
+/// Dart 3 extension type, e.g. `extension type Id(int value) {}`.
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
+pub struct ExtensionTypeDeclaration {
+    pub tag: ExtensionTypeDeclarationTag,
+    pub canonical_name: CanonicalNameRef,
+    pub name: StringRef,
+    #[declio(with = "codecs::list")]
+    pub annotations: Vec<Expr>,
+    pub file_uri: UriRef,
+    pub offset: FileOffset,
+    pub flags: ExtensionTypeDeclarationFlags,
+    #[declio(with = "codecs::list")]
+    pub type_params: Vec<TypeParameter>,
+    pub representation_type: Type,
+    pub representation_name: StringRef,
+    #[declio(with = "codecs::list")]
+    pub implements: Vec<Type>,
+    #[declio(with = "codecs::list")]
+    pub procedures: Vec<Procedure>,
+    #[declio(with = "codecs::list")]
+    pub members: Vec<ExtensionTypeMemberDescriptor>,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+pub struct ExtensionTypeMemberDescriptor {
+    pub name: StringRef,
+    pub kind: ExtensionTypeMemberKind,
+    pub flags: MemberDescriptorFlags,
+    pub member: CanonicalNameRef,
+    pub tear_off: CanonicalNameRef,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[declio(id_type = "u8")]
+pub enum ExtensionTypeMemberKind {
+    #[declio(id = "0")]
+    Constructor,
+    #[declio(id = "1")]
+    Factory,
+    #[declio(id = "2")]
+    Field,
+    #[declio(id = "3")]
+    Method,
+    #[declio(id = "4")]
+    Getter,
+    #[declio(id = "5")]
+    Setter,
+    #[declio(id = "6")]
+    Operator,
+    #[declio(id = "7")]
+    RedirectingFactory,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct Field {
     pub tag: FieldTag,
     pub canonical_name: CanonicalNameRef,
@@ -90,7 +181,7 @@ pub struct Field {
     pub initializer: Option<Box<Expr>>,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct Constructor {
     pub tag: ConstructorTag,
     pub canonical_name: CanonicalNameRef,
@@ -106,11 +197,25 @@ pub struct Constructor {
     pub initializers: Vec<Initializer>,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
-// TODO
-pub struct RedirectingFactory {}
+/// Factory constructor that redirects to another constructor, e.g. `factory Foo() = Bar;`.
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
+pub struct RedirectingFactory {
+    pub tag: RedirectingFactoryTag,
+    pub canonical_name: CanonicalNameRef,
+    pub file_uri: UriRef,
+    pub offset: FileOffset,
+    pub end_offset: FileOffset,
+    pub flags: RedirectingFactoryFlags,
+    pub name: StringRef,
+    #[declio(with = "codecs::list")]
+    pub annotations: Vec<Expr>,
+    pub target: CanonicalNameRef,
+    #[declio(with = "codecs::list")]
+    pub type_args: Vec<Type>,
+    pub function: Box<Function>,
+}
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct Function {
     pub tag: FunctionTag,
     pub file_range: FileRange,
@@ -127,11 +232,11 @@ pub struct Function {
     pub return_type: Type,
     #[declio(with = "codecs::option")]
     pub future_value_type: Option<Type>,
-    #[declio(with = "codecs::option")]
+    #[declio(with = "codecs::body")]
     pub body: Option<Box<Stmt>>,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct Typedef {
     pub canonical_name: CanonicalNameRef,
     pub file_uri: UriRef,
@@ -150,45 +255,82 @@ pub struct Typedef {
     pub named_params: Vec<VarDecl>,
 }
 
-#[derive(Debug, Encode, Decode)]
-#[declio(id_type = "u8")]
-pub enum Type {
-    #[declio(id = "98")]
-    Never(Nullable),
-    #[declio(id = "90")]
-    Invalid,
-    #[declio(id = "91")]
-    Dynamic,
-    #[declio(id = "92")]
-    Void,
-    #[declio(id = "93")]
-    GenericInterface {
-        nullable: Nullable,
-        class: CanonicalNameRef,
-        #[declio(with = "codecs::list")]
-        type_args: Vec<Type>,
-    },
-    #[declio(id = "96")]
-    Interface {
-        nullable: Nullable,
-        class: CanonicalNameRef,
-    },
-    #[declio(id = "94")]
-    GenericFunction(Box<FunctionType>),
-    #[declio(id = "97")]
-    Function {
-        nullable: Nullable,
-        #[declio(with = "codecs::list")]
-        positional_params: Vec<Type>,
-        return_type: Box<Type>,
-    },
-    #[declio(id = "95")]
-    TypeParam {
-        nullable: Nullable,
-        index: Uint,
-        #[declio(with = "codecs::option")]
-        bound: Option<Box<Type>>,
-    },
+codecs::node_enum! {
+    #[derive(Debug, PartialEq)]
+    pub enum Type {
+        #[tag = 98]
+        Never(Nullable),
+        #[tag = 90]
+        Invalid,
+        #[tag = 91]
+        Dynamic,
+        #[tag = 92]
+        Void,
+        #[tag = 93]
+        GenericInterface {
+            nullable: Nullable,
+            class: CanonicalNameRef,
+            #[declio(with = "codecs::list")]
+            type_args: Vec<Type>,
+        },
+        /// Interface type without type arguments.
+        #[tag = 96]
+        Interface {
+            nullable: Nullable,
+            class: CanonicalNameRef,
+        },
+        #[tag = 94]
+        GenericFunction(Box<FunctionType>),
+        /// Function type without type parameters or named parameters.
+        #[tag = 97]
+        Function {
+            nullable: Nullable,
+            #[declio(with = "codecs::list")]
+            positional_params: Vec<Type>,
+            return_type: Box<Type>,
+        },
+        #[tag = 95]
+        TypeParam {
+            nullable: Nullable,
+            index: Uint,
+            #[declio(with = "codecs::option")]
+            bound: Option<Box<Type>>,
+        },
+        #[tag = 107]
+        FutureOr {
+            nullable: Nullable,
+            type_arg: Box<Type>,
+        },
+        #[tag = 122]
+        Null,
+        #[tag = 87]
+        Typedef {
+            nullable: Nullable,
+            reference: CanonicalNameRef,
+            #[declio(with = "codecs::list")]
+            type_args: Vec<Type>,
+        },
+        /// Type parameter promoted to a subtype of its bound, where `left` is the
+        /// [`Type::TypeParam`].
+        #[tag = 99]
+        Intersection { left: Box<Type>, right: Box<Type> },
+        #[tag = 103]
+        Extension {
+            nullable: Nullable,
+            reference: CanonicalNameRef,
+            #[declio(with = "codecs::list")]
+            type_args: Vec<Type>,
+        },
+        /// Named fields are sorted by name.
+        #[tag = 100]
+        Record {
+            nullable: Nullable,
+            #[declio(with = "codecs::list")]
+            positional: Vec<Type>,
+            #[declio(with = "codecs::list")]
+            named: Vec<NamedType>,
+        },
+    }
 }
 
 impl Default for Type {
@@ -197,7 +339,7 @@ impl Default for Type {
     }
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct FunctionType {
     pub nullable: Nullable,
     #[declio(with = "codecs::list")]
@@ -213,14 +355,14 @@ pub struct FunctionType {
     pub return_type: Type,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct NamedType {
     pub name: StringRef,
     pub typ: Type,
     pub flags: u8,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct TypeParameter {
     pub flags: u8,
     #[declio(with = "codecs::list")]
@@ -231,7 +373,7 @@ pub struct TypeParameter {
     pub default_type: Type,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
 pub struct TypedefType {
     pub tag: TypedefTypeTag,
     pub nullable: Nullable,
@@ -240,18 +382,40 @@ pub struct TypedefType {
     pub type_args: Vec<Type>,
 }
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 pub struct Combinator {
     pub flags: CombinatorFlags,
     #[declio(with = "codecs::list")]
     pub names: Vec<StringRef>,
 }
 
-#[derive(Debug, Default, Encode, Decode)]
-// TODO
-pub struct ExtensionShowClause {}
+/// Members and supertypes shown or hidden by an extension, only serialized before
+/// [`Version::NO_SHOW_HIDE`].
+#[derive(Debug, Default, PartialEq, Encode, Decode)]
+pub struct ExtensionShowHideClause {
+    #[declio(with = "codecs::list")]
+    pub shown_supertypes: Vec<Type>,
+    #[declio(with = "codecs::list")]
+    pub shown_members: Vec<CanonicalNameRef>,
+    #[declio(with = "codecs::list")]
+    pub shown_getters: Vec<CanonicalNameRef>,
+    #[declio(with = "codecs::list")]
+    pub shown_setters: Vec<CanonicalNameRef>,
+    #[declio(with = "codecs::list")]
+    pub shown_operators: Vec<CanonicalNameRef>,
+    #[declio(with = "codecs::list")]
+    pub hidden_supertypes: Vec<Type>,
+    #[declio(with = "codecs::list")]
+    pub hidden_members: Vec<CanonicalNameRef>,
+    #[declio(with = "codecs::list")]
+    pub hidden_getters: Vec<CanonicalNameRef>,
+    #[declio(with = "codecs::list")]
+    pub hidden_setters: Vec<CanonicalNameRef>,
+    #[declio(with = "codecs::list")]
+    pub hidden_operators: Vec<CanonicalNameRef>,
+}
 
-#[derive(Debug, Encode, Decode)]
+#[derive(Debug, PartialEq, Encode, Decode)]
 #[declio(id_type = "u8")]
 pub enum Initializer {
     #[declio(id = "7")]
@@ -360,22 +524,226 @@ pub enum Variance {
     Invariant,
 }
 
-#[derive(Debug, Default)]
-pub struct ClassIndex;
+/// Offset table trailing a class. Offsets are relative to the start of the component and include
+/// the end offset of the last procedure.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ClassIndex {
+    pub procedure_offsets: Vec<u32>,
+}
 
 impl Decode<usize> for ClassIndex {
     fn decode<R>(proc_count: usize, endian: Endian, reader: &mut R) -> Result<Self, declio::Error>
     where
         R: std::io::Read,
     {
-        for _ in 0..proc_count + 1 {
-            u32::decode((), endian, reader)?;
-        }
-        let size = u32::decode((), endian, reader)?;
-        if size as usize != proc_count {
-            Err(declio::Error::new("Index does not match procedure count"))
-        } else {
-            Ok(ClassIndex)
+        let procedure_offsets = codecs::offsets::decode(proc_count, endian, reader)?;
+        Ok(ClassIndex { procedure_offsets })
+    }
+}
+
+impl Member for Typedef {
+    fn segment(index: usize) -> PathSegment {
+        PathSegment::Typedef(index)
+    }
+}
+
+impl Member for Extension {
+    fn segment(index: usize) -> PathSegment {
+        PathSegment::Extension(index)
+    }
+}
+
+impl Member for ExtensionTypeDeclaration {
+    fn segment(index: usize) -> PathSegment {
+        PathSegment::ExtensionType(index)
+    }
+}
+
+impl Member for Field {
+    fn segment(index: usize) -> PathSegment {
+        PathSegment::Field(index)
+    }
+}
+
+impl Member for Constructor {
+    fn segment(index: usize) -> PathSegment {
+        PathSegment::Constructor(index)
+    }
+}
+
+impl Member for RedirectingFactory {
+    fn segment(index: usize) -> PathSegment {
+        PathSegment::RedirectingFactory(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::component::{ComponentFile, ComponentHeader, NonNullableMode};
+    use crate::metadata::{self, MetadataTarget};
+    use crate::testing::sample_builder;
+
+    #[test]
+    fn test_extensions_roundtrip() -> Result<(), Box<dyn Error>> {
+        let descriptor = |kind, member| ExtensionMemberDescriptor {
+            name: StringRef::UNDEFINED,
+            kind,
+            flags: MemberDescriptorFlags::new().with_is_static(true),
+            member,
+            tear_off: CanonicalNameRef::UNDEFINED,
+        };
+        let clause = |name| ExtensionShowHideClause {
+            shown_members: vec![name],
+            hidden_supertypes: vec![Type::Dynamic],
+            hidden_getters: vec![name],
+            ..ExtensionShowHideClause::default()
+        };
+        for header in [
+            ComponentHeader::with_version(Version::V74),
+            ComponentHeader::DART_2_16_0_134,
+            ComponentHeader::with_version(Version::EXTENSION_TYPES),
+        ] {
+            let version = header.format_version();
+            let (mut builder, main_name) = sample_builder();
+            let method_name = builder.add_name_from([
+                StrRef::Borrowed("sample"),
+                StrRef::Borrowed("Ext"),
+                crate::names::METHODS,
+                StrRef::Borrowed("get"),
+            ]);
+            if version >= Version::EXTENSION_TYPES {
+                let tag = StrRef::Borrowed(metadata::UNREACHABLE);
+                builder.add_metadata(tag, MetadataTarget::Node(method_name), vec![]);
+            }
+            let mut comp = builder.into_component(main_name, NonNullableMode::Strong);
+            let lib = &mut comp.libraries[0];
+            lib.extensions.push(Extension {
+                flags: ExtensionFlags::new().with_is_unnamed_extension(true),
+                show_hide_clause: (version < Version::NO_SHOW_HIDE).then(|| clause(main_name)),
+                members: vec![
+                    descriptor(ExtensionMemberKind::Getter, main_name),
+                    descriptor(ExtensionMemberKind::TearOff, main_name),
+                ],
+                ..Extension::default()
+            });
+            if version >= Version::EXTENSION_TYPES {
+                lib.extension_types.push(ExtensionTypeDeclaration {
+                    representation_type: Type::Dynamic,
+                    procedures: vec![Procedure {
+                        canonical_name: method_name,
+                        ..Procedure::default()
+                    }],
+                    members: vec![ExtensionTypeMemberDescriptor {
+                        name: StringRef::UNDEFINED,
+                        kind: ExtensionTypeMemberKind::Constructor,
+                        flags: MemberDescriptorFlags::new(),
+                        member: main_name,
+                        tear_off: main_name,
+                    }],
+                    ..ExtensionTypeDeclaration::default()
+                });
+            }
+
+            let mut bytes = vec![];
+            comp.encode(&header, &mut bytes)?;
+            let comp = ComponentFile::from_bytes(bytes.as_slice())?.into_component()?;
+
+            let lib = &comp.libraries[0];
+            let kinds: Vec<_> = lib.extensions[0].members.iter().map(|m| m.kind).collect();
+            assert_eq!(kinds, [
+                ExtensionMemberKind::Getter,
+                ExtensionMemberKind::TearOff
+            ]);
+            assert!(lib.extensions[0].flags.is_unnamed_extension());
+            assert_eq!(
+                lib.extensions[0].show_hide_clause,
+                (version < Version::NO_SHOW_HIDE).then(|| clause(main_name))
+            );
+            assert_eq!(
+                lib.extension_types.len(),
+                (version >= Version::EXTENSION_TYPES) as usize
+            );
+            assert!(lib.procedures[0].decoded().is_some());
+
+            // metadata attached to a procedure of an extension type points at it
+            if let Some(repo) = comp.metadata.first() {
+                let MetadataTarget::Offset(offset) = repo.entries[0].node else {
+                    panic!("metadata should target an offset");
+                };
+                let proc = Procedure::decode((), ENDIANESS, &mut &bytes[offset as usize..])?;
+                assert_eq!(proc.canonical_name, method_name);
+            }
         }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_types_roundtrip() -> Result<(), Box<dyn Error>> {
+        let future_or = Type::FutureOr {
+            nullable: Nullable::False,
+            type_arg: Box::new(Type::Interface {
+                nullable: Nullable::False,
+                class: CanonicalNameRef::UNDEFINED,
+            }),
+        };
+        let mut bytes = vec![];
+        future_or.encode((), ENDIANESS, &mut bytes)?;
+        assert_eq!(bytes, vec![107, 1, 96, 1, 0]);
+
+        let types = vec![
+            future_or,
+            Type::Null,
+            Type::Typedef {
+                nullable: Nullable::True,
+                reference: CanonicalNameRef::UNDEFINED,
+                type_args: vec![Type::Dynamic],
+            },
+            Type::Intersection {
+                left: Box::new(Type::TypeParam {
+                    nullable: Nullable::Neither,
+                    index: Uint(0),
+                    bound: None,
+                }),
+                right: Box::new(Type::Null),
+            },
+            Type::Extension {
+                nullable: Nullable::False,
+                reference: CanonicalNameRef::UNDEFINED,
+                type_args: vec![],
+            },
+        ];
+        let mut bytes = vec![];
+        codecs::list::encode(&types, (), ENDIANESS, &mut bytes)?;
+        let decoded: Vec<Type> = codecs::list::decode((), ENDIANESS, &mut bytes.as_slice())?;
+        assert!(matches!(decoded[..], [
+            Type::FutureOr { .. },
+            Type::Null,
+            Type::Typedef { .. },
+            Type::Intersection { .. },
+            Type::Extension { .. }
+        ]));
+
+        // `FutureOr<Null>?` laid out as in binary.md:
+        // FutureOrType { Byte tag = 107; Byte nullability; DartType typeArgument; }
+        // NullType { Byte tag = 122; }
+        // where nullability 0 is `Nullability.nullable`
+        let spec = [107, 0, 122];
+        let future_or = Type::FutureOr {
+            nullable: Nullable::True,
+            type_arg: Box::new(Type::Null),
+        };
+        assert_eq!(
+            Type::decode((), ENDIANESS, &mut spec.as_slice())?,
+            future_or
+        );
+        let mut bytes = vec![];
+        future_or.encode((), ENDIANESS, &mut bytes)?;
+        assert_eq!(bytes, spec);
+
+        Ok(())
     }
 }
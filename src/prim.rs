@@ -1,11 +1,11 @@
 use std::hash::Hash;
 use std::marker::PhantomData;
-use std::rc::Rc;
 use std::{fmt, io};
 
 use declio::ctx::{Endian, Len};
 use declio::{magic_bytes, Decode, Encode};
 use hex_magic::hex;
+use wtf8::Wtf8;
 
 use crate::codecs;
 use crate::component::{LibraryDependency, SourceInfo};
@@ -14,22 +14,28 @@ use crate::expr::Constant;
 pub const ENDIANESS: Endian = Endian::Big;
 
 magic_bytes! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
     pub HeaderMagic(&hex!("90ABCDEF"));
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, PartialEq)]
     pub ClassTag(&hex!("02"));
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, PartialEq)]
     pub FunctionTag(&hex!("03"));
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, PartialEq)]
     pub FieldTag(&hex!("04"));
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, PartialEq)]
     pub ConstructorTag(&hex!("05"));
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, PartialEq)]
     pub ProcedureTag(&hex!("06"));
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, PartialEq)]
     pub ExtensionTag(&hex!("73"));
-    #[derive(Debug, Default)]
+    #[derive(Debug, Default, PartialEq)]
+    pub ExtensionTypeDeclarationTag(&hex!("55"));
+    #[derive(Debug, Default, PartialEq)]
     pub TypedefTypeTag(&hex!("57"));
+    #[derive(Debug, Default, PartialEq)]
+    pub RedirectingFactoryTag(&hex!("6C"));
+    #[derive(Debug, Default, PartialEq)]
+    pub AssertStatementTag(&hex!("40"));
 }
 
 #[derive(PartialEq, Eq, Hash, Encode, Decode)]
@@ -85,10 +91,67 @@ pub type ConstantRef = ComponentRef<Constant>;
 pub type DependencyRef = ComponentRef<LibraryDependency>;
 pub type VarRef = Uint;
 
-#[derive(Debug, Default, Clone, Copy, Encode, Decode)]
-pub struct FileOffset(pub Uint);
+/// Offset of a node in its source file. It is stored as `UInt(offset + 1)` so that 0 stands for
+/// a missing offset, which the VM reads as -1.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileOffset(pub Option<u32>);
 
-#[derive(Debug, Default, Clone, Encode, Decode)]
+impl FileOffset {
+    pub const NONE: Self = FileOffset(None);
+
+    #[inline]
+    pub const fn new(offset: u32) -> Self {
+        FileOffset(Some(offset))
+    }
+
+    #[inline]
+    pub fn get(self) -> Option<u32> {
+        self.0
+    }
+}
+
+impl From<u32> for FileOffset {
+    fn from(offset: u32) -> Self {
+        FileOffset::new(offset)
+    }
+}
+
+impl From<Option<u32>> for FileOffset {
+    fn from(offset: Option<u32>) -> Self {
+        FileOffset(offset)
+    }
+}
+
+impl From<FileOffset> for Option<u32> {
+    fn from(offset: FileOffset) -> Self {
+        offset.0
+    }
+}
+
+impl Decode for FileOffset {
+    fn decode<R>(ctx: (), endian: Endian, reader: &mut R) -> Result<Self, declio::Error>
+    where
+        R: io::Read,
+    {
+        let Uint(n) = Uint::decode(ctx, endian, reader)?;
+        Ok(FileOffset(n.checked_sub(1)))
+    }
+}
+
+impl Encode for FileOffset {
+    fn encode<W>(&self, ctx: (), endian: Endian, writer: &mut W) -> Result<(), declio::Error>
+    where
+        W: io::Write,
+    {
+        let n = match self.0 {
+            Some(offset) => offset.saturating_add(1),
+            None => 0,
+        };
+        Uint(n).encode(ctx, endian, writer)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Encode, Decode)]
 pub struct FileRange {
     pub start: FileOffset,
     pub end: FileOffset,
@@ -102,37 +165,94 @@ pub struct CanonicalName {
 
 pub type StrRef = CowRcStr<'static>;
 
+/// Pointer used for shared strings, atomically reference counted with the `sync` feature so that
+/// components can be sent across threads.
+#[cfg(not(feature = "sync"))]
+pub type Shared<A> = std::rc::Rc<A>;
+#[cfg(feature = "sync")]
+pub type Shared<A> = std::sync::Arc<A>;
+
 #[derive(Clone, Eq)]
 pub enum CowRcStr<'a> {
     Borrowed(&'a str),
-    Shared(Rc<String>),
+    Shared(Shared<String>),
+    /// WTF-8 bytes that are not valid UTF-8, such as Dart strings holding lone surrogates, along
+    /// with their lossy conversion to UTF-8.
+    Wtf8 {
+        bytes: Shared<Vec<u8>>,
+        lossy: Shared<String>,
+    },
 }
 
 impl<'a> CowRcStr<'a> {
+    /// Makes a string out of WTF-8 bytes, keeping the ones that are not valid UTF-8 as they are.
+    pub fn from_wtf8(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(str) => str.into(),
+            Err(err) => {
+                let bytes = err.into_bytes();
+                let lossy = Wtf8::from_bytes(&bytes).to_string_lossy().into_owned();
+                CowRcStr::Wtf8 {
+                    bytes: Shared::new(bytes),
+                    lossy: Shared::new(lossy),
+                }
+            }
+        }
+    }
+
+    /// Returns the string, with lone surrogates replaced by U+FFFD.
     pub fn as_str(&'a self) -> &'a str {
         match self {
             CowRcStr::Borrowed(str) => str,
             CowRcStr::Shared(rc) => rc.as_str(),
+            CowRcStr::Wtf8 { lossy, .. } => lossy.as_str(),
+        }
+    }
+
+    /// Returns the string unless it holds lone surrogates.
+    pub fn try_as_str(&'a self) -> Option<&'a str> {
+        match self {
+            CowRcStr::Wtf8 { .. } => None,
+            _ => Some(self.as_str()),
+        }
+    }
+
+    /// Returns the WTF-8 bytes of the string, as written to the string table.
+    pub fn as_bytes(&'a self) -> &'a [u8] {
+        match self {
+            CowRcStr::Borrowed(str) => str.as_bytes(),
+            CowRcStr::Shared(rc) => rc.as_bytes(),
+            CowRcStr::Wtf8 { bytes, .. } => bytes.as_slice(),
         }
     }
 
     pub fn to_owned<'b>(&'a self) -> CowRcStr<'b> {
         match self {
-            CowRcStr::Borrowed(str) => CowRcStr::Shared(Rc::new((*str).to_owned())),
+            CowRcStr::Borrowed(str) => CowRcStr::Shared(Shared::new((*str).to_owned())),
             CowRcStr::Shared(rc) => CowRcStr::Shared(rc.clone()),
+            CowRcStr::Wtf8 { bytes, lossy } => CowRcStr::Wtf8 {
+                bytes: bytes.clone(),
+                lossy: lossy.clone(),
+            },
         }
     }
 }
 
+impl From<String> for CowRcStr<'_> {
+    fn from(str: String) -> Self {
+        CowRcStr::Shared(Shared::new(str))
+    }
+}
+
 impl<'a> PartialEq for CowRcStr<'a> {
     fn eq(&self, other: &Self) -> bool {
-        self.as_str() == other.as_str()
+        self.as_bytes() == other.as_bytes()
     }
 }
 
 impl Hash for CowRcStr<'_> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.as_str().hash(state)
+        self.as_bytes().hash(state)
     }
 }
 
@@ -141,10 +261,56 @@ impl fmt::Debug for CowRcStr<'_> {
         match self {
             Self::Borrowed(str) => f.write_fmt(format_args!(r#""{str}""#)),
             Self::Shared(str) => f.write_fmt(format_args!(r#""{str}""#)),
+            Self::Wtf8 { bytes, .. } => fmt::Debug::fmt(Wtf8::from_bytes(bytes), f),
+        }
+    }
+}
+
+/// Kernel binary format version, passed as the codec context to nodes whose layout depends on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(pub u32);
+
+impl Version {
+    /// Dart 2.15.
+    pub const V74: Version = Version(74);
+    /// Dart 2.16 and 2.17.
+    pub const V75: Version = Version(75);
+    /// Extension show/hide clauses are no longer serialized.
+    pub const NO_SHOW_HIDE: Version = Version(76);
+    /// Class flags are widened to a `Uint` to fit the Dart 3 class modifiers.
+    pub const CLASS_MODIFIERS: Version = Version(90);
+    /// Libraries hold extension type declarations, and extension member descriptors reference
+    /// their tear-offs.
+    pub const EXTENSION_TYPES: Version = Version(106);
+
+    pub const MIN_SUPPORTED: Version = Self::V74;
+    pub const MAX_SUPPORTED: Version = Self::EXTENSION_TYPES;
+
+    #[inline]
+    pub fn is_supported(self) -> bool {
+        (Self::MIN_SUPPORTED..=Self::MAX_SUPPORTED).contains(&self)
+    }
+
+    pub fn check_supported(self) -> Result<Self, declio::Error> {
+        if self.is_supported() {
+            Ok(self)
+        } else {
+            Err(declio::Error::new(format_args!(
+                "Unsupported kernel format version {} (supported: {}-{})",
+                self.0,
+                Self::MIN_SUPPORTED.0,
+                Self::MAX_SUPPORTED.0
+            )))
         }
     }
 }
 
+impl From<u32> for Version {
+    fn from(n: u32) -> Self {
+        Version(n)
+    }
+}
+
 #[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Uint(pub u32);
 
@@ -191,17 +357,22 @@ impl Encode for Uint {
         _endian: Endian,
         writer: &mut W,
     ) -> Result<(), declio::Error> {
-        if self.0 < 0b01111111 {
+        if self.0 < 1 << 7 {
             u8::encode(&(self.0 as u8), ctx, ENDIANESS, writer)
-        } else if self.0 < 0b0011_1111_1111_1111 {
+        } else if self.0 < 1 << 14 {
             u16::encode(&(self.0 as u16 | 1 << 15), ctx, ENDIANESS, writer)
-        } else {
+        } else if self.0 < 1 << 30 {
             u32::encode(&(self.0 | 3 << 30), ctx, ENDIANESS, writer)
+        } else {
+            Err(declio::Error::new(format_args!(
+                "Value {} does not fit in a UInt",
+                self.0
+            )))
         }
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Utf8(pub String);
 
 impl Encode for Utf8 {
@@ -232,6 +403,8 @@ mod tests {
     use std::error::Error;
 
     use super::*;
+    use crate::component::Component;
+    use crate::testing::write_and_load;
 
     #[test]
     fn test_roundtrips() -> Result<(), Box<dyn Error>> {
@@ -244,6 +417,28 @@ mod tests {
         check_roundtrip(Uint(4321))?;
         check_roundtrip(Uint(123456))?;
         check_roundtrip(Uint(87654321))?;
+        check_roundtrip(Uint(127))?;
+        check_roundtrip(Uint(16383))?;
+        check_roundtrip(Uint((1 << 30) - 1))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_lone_surrogates() -> Result<(), Box<dyn Error>> {
+        // "a\uD800" encoded as WTF-8
+        let str = StrRef::from_wtf8(vec![b'a', 0xED, 0xA0, 0x80]);
+        assert_eq!(str.try_as_str(), None);
+        assert_eq!(str.as_str(), "a\u{FFFD}");
+        let mut comp = Component::default();
+        comp.strings.push(str.clone());
+
+        let (bytes, loaded) = write_and_load(&comp)?;
+        let loaded = loaded.into_component()?;
+        assert_eq!(loaded.strings, [str]);
+
+        let (rewritten, _) = write_and_load(&loaded)?;
+        assert_eq!(rewritten, bytes);
+
         Ok(())
     }
 
@@ -253,15 +448,50 @@ mod tests {
         assert_eq!(bytes, vec![0xC]);
         let bytes = declio::to_bytes(Uint(81), Endian::Big)?;
         assert_eq!(bytes, vec![0x51]);
+        let bytes = declio::to_bytes(Uint(127), Endian::Big)?;
+        assert_eq!(bytes, vec![0x7F]);
+        let bytes = declio::to_bytes(Uint(128), Endian::Big)?;
+        assert_eq!(bytes, vec![0x80, 0x80]);
         let bytes = declio::to_bytes(Uint(4321), Endian::Big)?;
         assert_eq!(bytes, vec![0x90, 0xE1]);
+        let bytes = declio::to_bytes(Uint(16383), Endian::Big)?;
+        assert_eq!(bytes, vec![0xBF, 0xFF]);
+        let bytes = declio::to_bytes(Uint(16384), Endian::Big)?;
+        assert_eq!(bytes, vec![0xC0, 0x0, 0x40, 0x0]);
         let bytes = declio::to_bytes(Uint(123456), Endian::Big)?;
         assert_eq!(bytes, vec![0xC0, 0x1, 0xE2, 0x40]);
         let bytes = declio::to_bytes(Uint(87654321), Endian::Big)?;
         assert_eq!(bytes, vec![0xC5, 0x39, 0x7F, 0xB1]);
+        assert!(declio::to_bytes(Uint(1 << 30), Endian::Big).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_offsets() -> Result<(), Box<dyn Error>> {
+        let bytes = declio::to_bytes(FileOffset::NONE, Endian::Big)?;
+        assert_eq!(bytes, vec![0x0]);
+        let bytes = declio::to_bytes(FileOffset::new(0), Endian::Big)?;
+        assert_eq!(bytes, vec![0x1]);
+        let bytes = declio::to_bytes(FileOffset::new(200), Endian::Big)?;
+        assert_eq!(bytes, vec![0x80, 0xC9]);
+
+        let decoded: FileOffset = declio::from_bytes(&[0x0], Endian::Big)?;
+        assert_eq!(decoded.get(), None);
+        let decoded: FileOffset = declio::from_bytes(&[0x80, 0xC9], Endian::Big)?;
+        assert_eq!(decoded.get(), Some(200));
         Ok(())
     }
 
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_thread_safety() {
+        fn assert_send_sync<A: Send + Sync>() {}
+        assert_send_sync::<StrRef>();
+        assert_send_sync::<crate::component::Component>();
+        assert_send_sync::<crate::component::Undecodable>();
+        assert_send_sync::<crate::builder::ComponentBuilder>();
+    }
+
     fn check_roundtrip(val: Uint) -> Result<(), Box<dyn Error>> {
         let encoded = declio::to_bytes(val, Endian::Big)?;
         let decoded: Uint = declio::from_bytes(&encoded, Endian::Big)?;
@@ -0,0 +1,154 @@
+use crate::expr::{Constant, Expr, Stmt};
+use crate::node::Type;
+
+/// Tag of a node in the binary format, along with the enum variant that decodes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tag {
+    /// Name of the variant in this crate.
+    pub variant: &'static str,
+    pub id: u8,
+}
+
+/// Looks up the tag of a variant in one of the tables of this module.
+pub fn find(table: &[Tag], variant: &str) -> Option<Tag> {
+    table.iter().find(|tag| tag.variant == variant).copied()
+}
+
+/// Tags of [`Expr`]. Tags from [`FIRST_CRATE_TAG`](crate::expr::FIRST_CRATE_TAG) up are specific
+/// to this crate, see [`Pattern`](crate::expr::Pattern).
+pub const EXPR: &[Tag] = Expr::TAGS;
+
+/// Tags of [`Stmt`]. Tags from [`FIRST_CRATE_TAG`](crate::expr::FIRST_CRATE_TAG) up are specific
+/// to this crate, see [`Pattern`](crate::expr::Pattern).
+pub const STMT: &[Tag] = Stmt::TAGS;
+
+/// Tags of [`Type`].
+pub const TYPE: &[Tag] = Type::TAGS;
+
+/// Tags of [`Constant`].
+pub const CONSTANT: &[Tag] = Constant::TAGS;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use declio::{Decode, Encode};
+
+    use super::*;
+    use crate::expr::{Arguments, Assert, Expr, InstanceCreate, TaggedAssert};
+    use crate::node::Type;
+    use crate::prim::{
+        AssertStatementTag, CanonicalNameRef, ConstantRef, FileOffset, FileRange, StringRef, UriRef, ENDIANESS
+    };
+
+    /// Expressions, statements and types share the tag byte, but each is read from its own
+    /// namespace, so tags only need to be unique within an enum.
+    #[test]
+    fn test_unique_tags() {
+        let tables = [
+            ("Expr", EXPR),
+            ("Stmt", STMT),
+            ("Type", TYPE),
+            ("Constant", CONSTANT),
+        ];
+        for (name, table) in tables {
+            let mut seen = HashMap::new();
+            for tag in table {
+                if let Some(other) = seen.insert(tag.id, tag.variant) {
+                    let (variant, id) = (tag.variant, tag.id);
+                    panic!("{name}::{variant} and {name}::{other} share the tag {id}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tagged_assert() -> Result<(), declio::Error> {
+        let tagged = TaggedAssert {
+            tag: AssertStatementTag::default(),
+            assert: Assert {
+                condition: Box::new(Expr::TrueLit),
+                file_range: FileRange::default(),
+                message: None,
+            },
+        };
+        let mut bytes = vec![];
+        tagged.encode((), ENDIANESS, &mut bytes)?;
+        assert_eq!(Some(bytes[0]), find(STMT, "Assert").map(|tag| tag.id));
+        assert_eq!(
+            TaggedAssert::decode((), ENDIANESS, &mut bytes.as_slice())?,
+            tagged
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_distinct_variants_roundtrip() -> Result<(), declio::Error> {
+        let exprs = vec![
+            Expr::SuperPropGet {
+                offset: FileOffset::NONE,
+                name: StringRef::UNDEFINED,
+                interface_target: CanonicalNameRef::UNDEFINED,
+                interface_target_origin: CanonicalNameRef::UNDEFINED,
+            },
+            Expr::SuperPropSet {
+                offset: FileOffset::NONE,
+                name: StringRef::UNDEFINED,
+                value: Box::new(Expr::NullLit),
+                interface_target: CanonicalNameRef::UNDEFINED,
+                interface_target_origin: CanonicalNameRef::UNDEFINED,
+            },
+            Expr::AbstractSuperPropGet {
+                offset: FileOffset::NONE,
+                name: StringRef::UNDEFINED,
+                interface_target: CanonicalNameRef::UNDEFINED,
+                interface_target_origin: CanonicalNameRef::UNDEFINED,
+            },
+            Expr::AbstractSuperMethodInvoke {
+                offset: FileOffset::NONE,
+                name: StringRef::UNDEFINED,
+                arguments: Box::new(Arguments::positional(vec![], vec![Expr::IntLit0])),
+                interface_target: CanonicalNameRef::UNDEFINED,
+                interface_target_origin: CanonicalNameRef::UNDEFINED,
+            },
+            Expr::ConstListLit {
+                offset: FileOffset::NONE,
+                type_argument: Box::new(Type::Dynamic),
+                values: vec![Expr::TrueLit],
+            },
+            Expr::FileUriConstant {
+                file_uri: UriRef::UNDEFINED,
+                offset: FileOffset::NONE,
+                typ: Box::new(Type::Dynamic),
+                constant: ConstantRef::UNDEFINED,
+            },
+            Expr::InstanceCreate(Box::new(InstanceCreate {
+                offset: FileOffset::NONE,
+                class: CanonicalNameRef::UNDEFINED,
+                type_args: vec![],
+                field_values: vec![],
+                asserts: vec![TaggedAssert {
+                    tag: AssertStatementTag::default(),
+                    assert: Assert {
+                        condition: Box::new(Expr::TrueLit),
+                        file_range: FileRange::default(),
+                        message: None,
+                    },
+                }],
+                unused_args: vec![],
+            })),
+        ];
+
+        for expr in exprs {
+            let mut bytes = vec![];
+            expr.encode((), ENDIANESS, &mut bytes)?;
+            let decoded = Expr::decode((), ENDIANESS, &mut bytes.as_slice())?;
+            assert_eq!(
+                std::mem::discriminant(&decoded),
+                std::mem::discriminant(&expr)
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! Components shared by the tests of the other modules.
+
+use std::error::Error;
+use std::io::Cursor;
+
+use crate::builder::ComponentBuilder;
+use crate::component::{
+    Component, ComponentFile, ComponentHeader, Library, NonNullableMode, SourceInfo
+};
+use crate::expr::{Arguments, Expr, Stmt};
+use crate::node::{Class, Function, Procedure};
+use crate::prim::{CanonicalNameRef, StrRef};
+
+/// Writes a component with the header of Dart 2.16 and loads it back from the written bytes.
+pub(crate) fn write_and_load(
+    comp: &Component,
+) -> Result<(Vec<u8>, ComponentFile<Cursor<Vec<u8>>>), Box<dyn Error>> {
+    let mut bytes = vec![];
+    comp.encode(&ComponentHeader::DART_2_16_0_134, &mut bytes)?;
+    let loaded = ComponentFile::from_bytes(bytes.clone())?;
+    Ok((bytes, loaded))
+}
+
+/// Component of [`sample_builder`], along with the canonical name of its `main`.
+pub(crate) fn sample_component() -> (Component, CanonicalNameRef) {
+    let (builder, main_name) = sample_builder();
+    (
+        builder.into_component(main_name, NonNullableMode::Strong),
+        main_name,
+    )
+}
+
+/// Bytes of the sample component, along with a copy in which the procedure of its class is
+/// corrupted.
+pub(crate) fn sample_bytes() -> Result<(Vec<u8>, Vec<u8>), Box<dyn Error>> {
+    let (bytes, mut loaded) = write_and_load(&sample_component().0)?;
+    let entry = loaded.library_entries()?.remove(0);
+    // the procedure ends with the option tag of its empty body
+    let mut corrupted = bytes.clone();
+    corrupted[entry.classes[0].procedures[0].range.end as usize - 1] = 0xFF;
+    Ok((bytes, corrupted))
+}
+
+/// Library `sample` with a `main` printing a message and a class `Foo` with an empty procedure
+/// `bar`, along with the canonical name of `main`.
+pub(crate) fn sample_builder() -> (ComponentBuilder, CanonicalNameRef) {
+    let mut builder = ComponentBuilder::default();
+    builder.add_source(SourceInfo::default());
+
+    let lib_name = builder.add_name_from([StrRef::Borrowed("sample")]);
+    let main_name = builder.add_name_from([
+        StrRef::Borrowed("sample"),
+        crate::names::METHODS,
+        crate::names::MAIN,
+    ]);
+    let print_name = builder.add_name_from([
+        crate::names::DART_CORE,
+        crate::names::METHODS,
+        StrRef::Borrowed("print"),
+    ]);
+    let message = builder.add_string(StrRef::Borrowed("Hello world!"));
+
+    let body = Expr::StaticInvoke {
+        offset: Default::default(),
+        target: print_name,
+        arguments: Box::new(Arguments::positional(
+            vec![],
+            vec![Expr::StringLit(message)],
+        )),
+    };
+    let proc = Procedure {
+        canonical_name: main_name,
+        function: Box::new(Function {
+            body: Some(Box::new(Stmt::Expr(Box::new(body)))),
+            ..Function::default()
+        }),
+        ..Procedure::default()
+    };
+    let class_name = builder.add_name_from([StrRef::Borrowed("sample"), StrRef::Borrowed("Foo")]);
+    let method_name = builder.add_name_from([
+        StrRef::Borrowed("sample"),
+        StrRef::Borrowed("Foo"),
+        crate::names::METHODS,
+        StrRef::Borrowed("bar"),
+    ]);
+    let class = Class {
+        canonical_name: class_name,
+        name: builder.add_string(StrRef::Borrowed("Foo")),
+        procedures: vec![Procedure {
+            canonical_name: method_name,
+            name: builder.add_string(StrRef::Borrowed("bar")),
+            ..Procedure::default()
+        }
+        .into()],
+        ..Class::default()
+    };
+    builder.add_library(Library {
+        canonical_name: lib_name,
+        classes: vec![class.into()],
+        procedures: vec![proc.into()],
+        ..Library::default()
+    });
+    (builder, main_name)
+}
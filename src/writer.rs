@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::io;
 
 use declio::ctx::{Endian, Len};
@@ -5,54 +6,158 @@ use declio::Encode;
 
 use crate::codecs;
 use crate::component::*;
-use crate::node::Class;
-use crate::prim::{CowRcStr, Uint, ENDIANESS};
+use crate::expr::{Expr, Stmt, FIRST_CRATE_TAG};
+use crate::metadata::{ExprOffsets, NodeOffsets};
+use crate::node::{Class, ExtensionTypeDeclaration};
+use crate::prim::{CowRcStr, Uint, Version, ENDIANESS};
+
+thread_local! {
+    /// Component being written on this thread. Expressions are written by their own codec, which
+    /// only sees a generic writer, so the writer keeps its position here for them.
+    static SESSION: RefCell<Option<Session>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Default)]
+struct Session {
+    position: u32,
+    exprs: Vec<(usize, u32)>,
+    crate_specific_tags: bool,
+}
+
+pub fn write_component<W: io::Write>(
+    header: &ComponentHeader,
+    comp: &Component,
+    opts: &EncodeOptions,
+    out: &mut W,
+) -> Result<ExprOffsets, declio::Error> {
+    let session = Session {
+        crate_specific_tags: opts.crate_specific_tags,
+        ..Session::default()
+    };
+    SESSION.with(|cell| *cell.borrow_mut() = Some(session));
+    let res = write_sections(header, comp, out);
+    let session = SESSION.with(|session| session.borrow_mut().take());
+    res?;
+    Ok(ExprOffsets::new(session.unwrap_or_default().exprs))
+}
 
-pub fn write_component<W: io::Write + io::Seek>(
+/// Records the offset of an expression written by [`write_component`], called by the codec of
+/// [`Expr`]. Expressions encoded on their own are not recorded.
+pub(crate) fn record_expr(expr: &Expr) -> Result<(), declio::Error> {
+    check_crate_tag("expression", expr.tag())?;
+    SESSION.with(|session| {
+        if let Some(session) = &mut *session.borrow_mut() {
+            let offset = session.position;
+            session.exprs.push((expr as *const Expr as usize, offset));
+        }
+    });
+    Ok(())
+}
+
+/// Called by the codec of [`Stmt`].
+pub(crate) fn check_stmt(stmt: &Stmt) -> Result<(), declio::Error> {
+    check_crate_tag("statement", stmt.tag())
+}
+
+/// Rejects the tags specific to this crate within [`write_component`], unless they were opted into.
+fn check_crate_tag(node: &str, tag: u8) -> Result<(), declio::Error> {
+    let allowed = SESSION.with(|session| match &*session.borrow() {
+        Some(session) => session.crate_specific_tags || tag < FIRST_CRATE_TAG,
+        None => true,
+    });
+    if !allowed {
+        return Err(declio::Error::new(format_args!(
+            "The {node} tag {tag} is specific to this crate, set \
+             EncodeOptions::crate_specific_tags to write it"
+        )));
+    }
+    Ok(())
+}
+
+fn write_sections<W: io::Write>(
     header: &ComponentHeader,
     comp: &Component,
     out: &mut W,
 ) -> Result<(), declio::Error> {
+    let version = header.format_version().check_supported()?;
+    let out = &mut CountingWriter::new(out, 0).in_session();
     header.encode((), ENDIANESS, out)?;
 
     codecs::list::encode(&comp.problems, (), ENDIANESS, out)?;
 
+    let mut nodes = NodeOffsets::default();
     let mut library_offsets = write_keeping_offsets(
         &comp.libraries,
-        |lib, _, _, out| write_library(lib, out),
+        |lib, _, _, out| write_library(lib, version, &mut nodes, out),
         out,
     )?;
-    library_offsets.push(out.stream_position()? as u32);
-
-    let source_table_offset = out.stream_position()? as u32;
+    nodes.record(
+        comp.libraries
+            .iter()
+            .map(|lib| lib.canonical_name)
+            .zip(library_offsets.iter().copied()),
+    );
+    library_offsets.push(out.position());
+
+    let source_table_offset = out.position();
     (comp.source_map.len() as u32).encode((), ENDIANESS, out)?;
     let source_offsets = write_keeping_offsets(&comp.source_map, Encode::encode, out)?;
     source_offsets.encode(Len(comp.source_map.len()), ENDIANESS, out)?;
 
-    let constant_table_offset = out.stream_position()? as u32;
+    let constant_table_offset = out.position();
     Uint(comp.constants.len() as u32).encode((), ENDIANESS, out)?;
-    let constant_table_index_offset = out.stream_position()? as u32;
     let constant_offsets = write_keeping_offsets(&comp.constants, Encode::encode, out)?;
+
+    // offsets in the constant table index are relative to the start of the constant table
+    let constant_table_index_offset = out.position();
+    for offset in &constant_offsets {
+        (offset - constant_table_offset).encode((), ENDIANESS, out)?;
+    }
     (constant_offsets.len() as u32).encode((), ENDIANESS, out)?;
 
-    let canonical_names_offset = out.stream_position()? as u32;
+    let canonical_names_offset = out.position();
     codecs::list::encode(&comp.canonical_names, (), ENDIANESS, out)?;
 
-    let metadata_payloads_offset = out.stream_position()? as u32;
-    let metadata_offsets = write_keeping_offsets(
-        &comp.payloads,
-        |payload, _, _, out| out.write_all(payload).map_err(Into::into),
-        out,
-    )?;
-    let metadata_mappings_offset = out.stream_position()? as u32;
-    metadata_offsets.encode(Len(metadata_offsets.len()), ENDIANESS, out)?;
-    (metadata_offsets.len() as u32).encode((), ENDIANESS, out)?;
+    let metadata_payloads_offset = out.position();
+    let mut payloads: Vec<_> = comp
+        .metadata
+        .iter()
+        .enumerate()
+        .flat_map(|(i, repo)| repo.entries.iter().map(move |entry| (i, entry)))
+        .collect();
+    payloads.sort_by_key(|(_, entry)| (entry.payload_offset.is_none(), entry.payload_offset));
+
+    let mut mappings = vec![vec![]; comp.metadata.len()];
+    for (i, entry) in payloads {
+        let node_offset = nodes.resolve(entry.node)?;
+        let payload_offset = out.position() - metadata_payloads_offset;
+        out.write_all(&entry.payload)?;
+        mappings[i].push((node_offset, payload_offset));
+    }
 
-    let string_table_offset = out.stream_position()? as u32;
-    let string_table = StringTable::new(comp.strings.iter().map(CowRcStr::as_str))?;
+    let metadata_mappings_offset = out.position();
+    for (repo, pairs) in comp.metadata.iter().zip(&mut mappings) {
+        pairs.sort_by_key(|(node_offset, _)| *node_offset);
+        u32::from(repo.tag).encode((), ENDIANESS, out)?;
+        for (node_offset, payload_offset) in pairs {
+            node_offset.encode((), ENDIANESS, out)?;
+            payload_offset.encode((), ENDIANESS, out)?;
+        }
+        (pairs.len() as u32).encode((), ENDIANESS, out)?;
+    }
+    (mappings.len() as u32).encode((), ENDIANESS, out)?;
+
+    let string_table_offset = out.position();
+    let string_table = StringTable::new(comp.strings.iter().map(CowRcStr::as_bytes))?;
     string_table.encode((), ENDIANESS, out)?;
 
-    let component_index_offset = out.stream_position()? as u32;
+    // the component index is padded so that the size of the whole component is a multiple of 8
+    let component_index_offset = out.position();
+    let index_size =
+        ComponentIndex::MINIMUM_SIZE + comp.libraries.len() * 4 + ComponentMetadata::SIZE;
+    let padding = (8 - (component_index_offset as usize + index_size) % 8) % 8;
+    out.write_all(&[0; 8][..padding])?;
+
     let index = ComponentIndex {
         source_table_offset,
         constant_table_offset,
@@ -69,7 +174,7 @@ pub fn write_component<W: io::Write + io::Seek>(
     let library_count = comp.libraries.len() as u32;
     index.encode(library_count, ENDIANESS, out)?;
 
-    let file_size = out.stream_position()? as u32 + ComponentMetadata::SIZE as u32;
+    let file_size = out.position() + ComponentMetadata::SIZE as u32;
     let metadata = ComponentMetadata {
         library_count,
         file_size,
@@ -77,7 +182,12 @@ pub fn write_component<W: io::Write + io::Seek>(
     metadata.encode((), ENDIANESS, out)
 }
 
-fn write_library<W: io::Write + io::Seek>(lib: &Library, out: &mut W) -> Result<(), declio::Error> {
+fn write_library<W: io::Write>(
+    lib: &Library,
+    version: Version,
+    nodes: &mut NodeOffsets,
+    out: &mut CountingWriter<W>,
+) -> Result<(), declio::Error> {
     lib.flags.encode((), ENDIANESS, out)?;
     lib.version_major.encode((), ENDIANESS, out)?;
     lib.version_minor.encode((), ENDIANESS, out)?;
@@ -92,71 +202,298 @@ fn write_library<W: io::Write + io::Seek>(lib: &Library, out: &mut W) -> Result<
     codecs::list::encode(&lib.typedefs, (), ENDIANESS, out)?;
 
     Uint(lib.classes.len() as u32).encode((), ENDIANESS, out)?;
-    let mut class_offsets =
-        write_keeping_offsets(&lib.classes, |item, _, _, out| write_class(item, out), out)?;
-    class_offsets.push(out.stream_position()? as u32);
+    let mut class_offsets = write_keeping_offsets(
+        &lib.classes,
+        |item, _, _, out| match item {
+            MaybeDecoded::Decoded(class) => write_class(class, version, nodes, out),
+            MaybeDecoded::Undecodable(class) => write_undecodable_class(class, out),
+        },
+        out,
+    )?;
+    nodes.record(
+        lib.classes
+            .iter()
+            .zip(class_offsets.iter().copied())
+            .filter_map(|(class, offset)| Some((class.decoded()?.canonical_name, offset))),
+    );
+    class_offsets.push(out.position());
+
+    codecs::list::encode(&lib.extensions, version, ENDIANESS, out)?;
+    if version >= Version::EXTENSION_TYPES {
+        Uint(lib.extension_types.len() as u32).encode((), ENDIANESS, out)?;
+        let decl_offsets = write_keeping_offsets(
+            &lib.extension_types,
+            |decl, _, _, out| write_extension_type(decl, nodes, out),
+            out,
+        )?;
+        nodes.record(
+            lib.extension_types
+                .iter()
+                .map(|decl| decl.canonical_name)
+                .zip(decl_offsets),
+        );
+    }
 
-    codecs::list::encode(&lib.extensions, (), ENDIANESS, out)?;
-    codecs::list::encode(&lib.fields, (), ENDIANESS, out)?;
+    Uint(lib.fields.len() as u32).encode((), ENDIANESS, out)?;
+    let field_offsets = write_keeping_offsets(&lib.fields, Encode::encode, out)?;
+    nodes.record(
+        lib.fields
+            .iter()
+            .map(|field| field.canonical_name)
+            .zip(field_offsets),
+    );
 
     Uint(lib.procedures.len() as u32).encode((), ENDIANESS, out)?;
-    let mut procedure_offsets = write_keeping_offsets(&lib.procedures, Encode::encode, out)?;
-    procedure_offsets.push(out.stream_position()? as u32);
-
-    let source_refs_offset = out.stream_position()? as u32;
+    let mut procedure_offsets = write_keeping_offsets(
+        &lib.procedures,
+        |item, _, _, out| match item {
+            MaybeDecoded::Decoded(proc) => proc.encode((), ENDIANESS, out),
+            MaybeDecoded::Undecodable(proc) => Ok(out.write_all(&proc.bytes)?),
+        },
+        out,
+    )?;
+    nodes.record(
+        lib.procedures
+            .iter()
+            .zip(procedure_offsets.iter().copied())
+            .filter_map(|(proc, offset)| Some((proc.decoded()?.canonical_name, offset))),
+    );
+    procedure_offsets.push(out.position());
+
+    let source_refs_offset = out.position();
     codecs::list::encode(&lib.source_refs, (), ENDIANESS, out)?;
 
     source_refs_offset.encode((), ENDIANESS, out)?;
-    write_offsets(&class_offsets, out)?;
-    write_offsets(&procedure_offsets, out)
+    codecs::offsets::encode(&class_offsets, ENDIANESS, out)?;
+    codecs::offsets::encode(&procedure_offsets, ENDIANESS, out)
 }
 
-fn write_class<W: io::Write + io::Seek>(class: &Class, out: &mut W) -> Result<(), declio::Error> {
+fn write_class<W: io::Write>(
+    class: &Class,
+    version: Version,
+    nodes: &mut NodeOffsets,
+    out: &mut CountingWriter<W>,
+) -> Result<(), declio::Error> {
     class.tag.encode((), ENDIANESS, out)?;
     class.canonical_name.encode((), ENDIANESS, out)?;
     class.file_uri.encode((), ENDIANESS, out)?;
     class.start_offset.encode((), ENDIANESS, out)?;
     class.definition_range.encode((), ENDIANESS, out)?;
-    class.flags.encode((), ENDIANESS, out)?;
+    class.flags.encode(version, ENDIANESS, out)?;
     class.name.encode((), ENDIANESS, out)?;
     codecs::list::encode(&class.annotations, (), ENDIANESS, out)?;
     codecs::list::encode(&class.type_params, (), ENDIANESS, out)?;
     codecs::option::encode(&class.super_class, (), ENDIANESS, out)?;
     codecs::option::encode(&class.mixed_in_type, (), ENDIANESS, out)?;
     codecs::list::encode(&class.implemented_classes, (), ENDIANESS, out)?;
-    codecs::list::encode(&class.fields, (), ENDIANESS, out)?;
-    codecs::list::encode(&class.constructors, (), ENDIANESS, out)?;
+
+    Uint(class.fields.len() as u32).encode((), ENDIANESS, out)?;
+    let field_offsets = write_keeping_offsets(&class.fields, Encode::encode, out)?;
+    nodes.record(
+        class
+            .fields
+            .iter()
+            .map(|field| field.canonical_name)
+            .zip(field_offsets),
+    );
+
+    Uint(class.constructors.len() as u32).encode((), ENDIANESS, out)?;
+    let ctor_offsets = write_keeping_offsets(&class.constructors, Encode::encode, out)?;
+    nodes.record(
+        class
+            .constructors
+            .iter()
+            .map(|ctor| ctor.canonical_name)
+            .zip(ctor_offsets),
+    );
 
     Uint(class.procedures.len() as u32).encode((), ENDIANESS, out)?;
-    let mut proc_offsets = write_keeping_offsets(&class.procedures, Encode::encode, out)?;
-    proc_offsets.push(out.stream_position()? as u32);
+    let mut proc_offsets = write_keeping_offsets(
+        &class.procedures,
+        |item, _, _, out| match item {
+            MaybeDecoded::Decoded(proc) => proc.encode((), ENDIANESS, out),
+            MaybeDecoded::Undecodable(proc) => Ok(out.write_all(&proc.bytes)?),
+        },
+        out,
+    )?;
+    nodes.record(
+        class
+            .procedures
+            .iter()
+            .zip(proc_offsets.iter().copied())
+            .filter_map(|(proc, offset)| Some((proc.decoded()?.canonical_name, offset))),
+    );
+    proc_offsets.push(out.position());
+
+    Uint(class.redirecting_factories.len() as u32).encode((), ENDIANESS, out)?;
+    let factory_offsets = write_keeping_offsets(&class.redirecting_factories, Encode::encode, out)?;
+    nodes.record(
+        class
+            .redirecting_factories
+            .iter()
+            .map(|factory| factory.canonical_name)
+            .zip(factory_offsets),
+    );
+
+    codecs::offsets::encode(&proc_offsets, ENDIANESS, out)
+}
 
-    codecs::list::encode(&class.redirecting_factories, (), ENDIANESS, out)?;
+fn write_extension_type<W: io::Write>(
+    decl: &ExtensionTypeDeclaration,
+    nodes: &mut NodeOffsets,
+    out: &mut CountingWriter<W>,
+) -> Result<(), declio::Error> {
+    decl.tag.encode((), ENDIANESS, out)?;
+    decl.canonical_name.encode((), ENDIANESS, out)?;
+    decl.name.encode((), ENDIANESS, out)?;
+    codecs::list::encode(&decl.annotations, (), ENDIANESS, out)?;
+    decl.file_uri.encode((), ENDIANESS, out)?;
+    decl.offset.encode((), ENDIANESS, out)?;
+    decl.flags.encode((), ENDIANESS, out)?;
+    codecs::list::encode(&decl.type_params, (), ENDIANESS, out)?;
+    decl.representation_type.encode((), ENDIANESS, out)?;
+    decl.representation_name.encode((), ENDIANESS, out)?;
+    codecs::list::encode(&decl.implements, (), ENDIANESS, out)?;
+
+    Uint(decl.procedures.len() as u32).encode((), ENDIANESS, out)?;
+    let proc_offsets = write_keeping_offsets(&decl.procedures, Encode::encode, out)?;
+    nodes.record(
+        decl.procedures
+            .iter()
+            .map(|proc| proc.canonical_name)
+            .zip(proc_offsets),
+    );
+
+    codecs::list::encode(&decl.members, (), ENDIANESS, out)
+}
 
-    write_offsets(&proc_offsets, out)
+/// Writes back the bytes of a class that could not be decoded, moving the procedure offsets
+/// trailing it to its new position.
+fn write_undecodable_class<W: io::Write>(
+    class: &Undecodable,
+    out: &mut CountingWriter<W>,
+) -> Result<(), declio::Error> {
+    let offset = out.position();
+    let mut bytes = class.bytes.clone();
+
+    let table = bytes
+        .len()
+        .checked_sub(4)
+        .and_then(|count_start| {
+            let count = u32::from_be_bytes(bytes[count_start..].try_into().ok()?);
+            let table_len = (count as usize).checked_add(1)?.checked_mul(4)?;
+            count_start
+                .checked_sub(table_len)
+                .map(|start| start..count_start)
+        })
+        .ok_or_else(|| declio::Error::new("Invalid procedure offsets of an undecodable class"))?;
+    for chunk in bytes[table].chunks_exact_mut(4) {
+        let old = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let new = old.wrapping_sub(class.offset).wrapping_add(offset);
+        chunk.copy_from_slice(&new.to_be_bytes());
+    }
+    out.write_all(&bytes)?;
+    Ok(())
 }
 
 fn write_keeping_offsets<A, W, F>(
     items: &[A],
-    encode: F,
-    out: &mut W,
+    mut encode: F,
+    out: &mut CountingWriter<W>,
 ) -> Result<Vec<u32>, declio::Error>
 where
-    W: io::Write + io::Seek,
-    F: Fn(&A, (), Endian, &mut W) -> Result<(), declio::Error>,
+    W: io::Write,
+    F: FnMut(&A, (), Endian, &mut CountingWriter<W>) -> Result<(), declio::Error>,
 {
     let mut offsets = Vec::with_capacity(items.len());
 
     for item in items {
-        offsets.push(out.stream_position()? as u32);
+        offsets.push(out.position());
         encode(item, (), ENDIANESS, out)?;
     }
     Ok(offsets)
 }
 
-fn write_offsets<W: io::Write>(offsets: &[u32], out: &mut W) -> Result<(), declio::Error> {
-    offsets.encode(Len(offsets.len()), ENDIANESS, out)?;
-    (offsets.len() as u32 - 1).encode((), ENDIANESS, out)
+/// Encodes a library on its own, with the offsets of its classes and procedures relative to the
+/// start of the library. The [`LibraryIndex`] is recomputed rather than copied.
+impl Encode<Version> for Library {
+    fn encode<W>(
+        &self,
+        version: Version,
+        _endian: Endian,
+        writer: &mut W,
+    ) -> Result<(), declio::Error>
+    where
+        W: io::Write,
+    {
+        let mut out = CountingWriter::new(writer, 0);
+        write_library(self, version, &mut NodeOffsets::default(), &mut out)
+    }
+}
+
+/// Encodes a class on its own, with the offsets of its procedures relative to the start of the
+/// class. The [`ClassIndex`](crate::node::ClassIndex) is recomputed rather than copied.
+impl Encode<Version> for Class {
+    fn encode<W>(
+        &self,
+        version: Version,
+        _endian: Endian,
+        writer: &mut W,
+    ) -> Result<(), declio::Error>
+    where
+        W: io::Write,
+    {
+        let mut out = CountingWriter::new(writer, 0);
+        write_class(self, version, &mut NodeOffsets::default(), &mut out)
+    }
+}
+
+/// Writer that keeps track of how many bytes went through it, so that offsets can be computed
+/// without seeking.
+pub(crate) struct CountingWriter<W> {
+    inner: W,
+    position: u32,
+    in_session: bool,
+}
+
+impl<W> CountingWriter<W> {
+    pub fn new(inner: W, position: u32) -> Self {
+        Self {
+            inner,
+            position,
+            in_session: false,
+        }
+    }
+
+    /// Keeps the position of the [`Session`] of this thread up to date.
+    fn in_session(mut self) -> Self {
+        self.in_session = true;
+        self
+    }
+
+    #[inline]
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+}
+
+impl<W: io::Write> io::Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.position += n as u32;
+        if self.in_session {
+            SESSION.with(|session| {
+                if let Some(session) = &mut *session.borrow_mut() {
+                    session.position = self.position;
+                }
+            });
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 #[cfg(test)]
@@ -164,8 +501,11 @@ mod tests {
     use std::error::Error;
     use std::io::Cursor;
 
+    use declio::Decode;
+
     use super::*;
     use crate::prim::StrRef;
+    use crate::testing::{sample_component, write_and_load};
 
     #[test]
     fn test_roundtrip() -> Result<(), Box<dyn Error>> {
@@ -187,4 +527,67 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_written_offsets() -> Result<(), Box<dyn Error>> {
+        let (_, mut loaded) = write_and_load(&sample_component().0)?;
+        let entry = loaded.library_entries()?.remove(0);
+        let lib = loaded.library(&entry)?;
+
+        assert_eq!(lib.index.class_offsets, [
+            entry.classes[0].range.start,
+            entry.classes[0].range.end
+        ]);
+        assert_eq!(lib.index.procedure_offsets, [
+            entry.procedures[0].range.start,
+            entry.procedures[0].range.end
+        ]);
+        assert_eq!(lib.index.source_refs_offset, entry.index.source_refs_offset);
+        assert!(lib.index.source_refs_offset >= entry.procedures[0].range.end);
+
+        let class = &entry.classes[0];
+        let procs = &class.procedures;
+        let decoded = lib.classes[0].decoded().expect("class not decoded");
+        assert_eq!(decoded.index.procedure_offsets, [
+            procs[0].range.start,
+            procs[0].range.end
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_standalone_encoding() -> Result<(), Box<dyn Error>> {
+        let (_, mut loaded) = write_and_load(&sample_component().0)?;
+        let version = loaded.version();
+        let entry = loaded.library_entries()?.remove(0);
+        let lib = loaded.library(&entry)?;
+
+        let mut bytes = vec![];
+        lib.encode(version, ENDIANESS, &mut bytes)?;
+        assert_eq!(bytes.len() as u32, entry.range.end - entry.range.start);
+
+        let decoded = Library::decode(version, ENDIANESS, &mut bytes.as_slice())?;
+        let relative = |offset: u32| offset - entry.range.start;
+        assert_eq!(decoded.index.class_offsets, [
+            relative(entry.classes[0].range.start),
+            relative(entry.classes[0].range.end)
+        ]);
+        assert_eq!(decoded.index.procedure_offsets, [
+            relative(entry.procedures[0].range.start),
+            relative(entry.procedures[0].range.end)
+        ]);
+
+        let class = lib.classes[0].decoded().expect("class not decoded");
+        let mut bytes = vec![];
+        class.encode(version, ENDIANESS, &mut bytes)?;
+        let decoded = Class::decode(version, ENDIANESS, &mut bytes.as_slice())?;
+        let bar = &entry.classes[0].procedures[0].range;
+        assert_eq!(decoded.index.procedure_offsets, [
+            bar.start - entry.classes[0].range.start,
+            bar.end - entry.classes[0].range.start
+        ]);
+
+        Ok(())
+    }
 }